@@ -0,0 +1,107 @@
+use crate::layers;
+use crate::pancam::PanCam;
+use crate::{BinaryPartner, Planet, Selected, Settings, Stats, Velocity};
+use bevy::{prelude::*, render::camera::OrthographicProjection};
+use bevy_prototype_lyon::prelude::*;
+use nbody::physics::{self, OrbitalElements};
+use std::f32::consts::PI;
+
+/// Relative orbit of the selected planet and the one pinned with shift+click as its binary
+/// partner, recomputed every frame. The osculating orbits of both planets around their
+/// barycenter are drawn as ellipses while the pair is bound.
+
+#[derive(Default)]
+pub struct BinaryPlugin;
+
+impl Plugin for BinaryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(track_binary);
+    }
+}
+
+/// Number of points of each drawn ellipse
+const ORBIT_POINTS: usize = 128;
+
+/// State of the partner relative to the selected planet, shown in the inspector.
+#[derive(Clone, Copy, Debug)]
+pub struct BinaryOrbit {
+    pub separation: f32,
+    pub relative_speed: f32,
+    /// `None` with repulsive gravity
+    pub elements: Option<OrbitalElements>,
+}
+
+#[derive(Component)]
+struct BinaryOrbitPath;
+
+fn track_binary(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    mut stats: ResMut<Stats>,
+    selected: Query<(Entity, &Planet, &Transform, &Velocity), With<Selected>>,
+    partner: Query<(Entity, &Planet, &Transform, &Velocity), With<BinaryPartner>>,
+    camera: Query<&OrthographicProjection, With<PanCam>>,
+    drawn: Query<Entity, With<BinaryOrbitPath>>,
+) {
+    for entity in drawn.iter() {
+        commands.entity(entity).despawn();
+    }
+    stats.binary_orbit = None;
+    let (primary, secondary) = match (selected.iter().next(), partner.iter().next()) {
+        (Some(primary), Some(secondary)) if primary.0 != secondary.0 => (primary, secondary),
+        _ => return,
+    };
+    let (_, planet_1, transform_1, velocity_1) = primary;
+    let (_, planet_2, transform_2, velocity_2) = secondary;
+    let (mass_1, mass_2) = (planet_1.mass(), planet_2.mass());
+    let position_1 = transform_1.translation.truncate();
+    let position_2 = transform_2.translation.truncate();
+    let relative_position = position_2 - position_1;
+    let relative_velocity = velocity_2.0 - velocity_1.0;
+    let elements = physics::orbital_elements(
+        settings.g * (mass_1 + mass_2),
+        relative_position,
+        relative_velocity,
+    );
+    stats.binary_orbit = Some(BinaryOrbit {
+        separation: relative_position.length(),
+        relative_speed: relative_velocity.length(),
+        elements,
+    });
+
+    let elements = match elements {
+        Some(elements) if elements.eccentricity < 1.0 => elements,
+        _ => return,
+    };
+    let pixel = match camera.iter().next() {
+        Some(projection) => projection.scale,
+        None => return,
+    };
+    // both planets follow the relative orbit around the barycenter, scaled by the mass of the other
+    let barycenter = (position_1 * mass_1 + position_2 * mass_2) / (mass_1 + mass_2);
+    let semi_latus_rectum = elements.semi_major_axis * (1.0 - elements.eccentricity.powi(2));
+    let relative_orbit: Vec<Vec2> = (0..ORBIT_POINTS)
+        .map(|i| {
+            let angle = i as f32 / ORBIT_POINTS as f32 * 2.0 * PI;
+            let r = semi_latus_rectum
+                / (1.0 + elements.eccentricity * (angle - elements.periapsis_angle).cos());
+            Vec2::new(angle.cos(), angle.sin()) * r
+        })
+        .collect();
+    let mut builder = PathBuilder::new();
+    for share in [-mass_2 / (mass_1 + mass_2), mass_1 / (mass_1 + mass_2)] {
+        builder.move_to(relative_orbit[0] * share);
+        for point in relative_orbit[1..].iter() {
+            builder.line_to(*point * share);
+        }
+        builder.close();
+    }
+    commands
+        .spawn_bundle(GeometryBuilder::build_as(
+            &builder.build(),
+            // one pixel wide regardless of the zoom
+            DrawMode::Stroke(StrokeMode::new(Color::rgba(1.0, 0.8, 0.4, 0.6), pixel)),
+            Transform::from_xyz(barycenter.x, barycenter.y, layers::OVERLAYS),
+        ))
+        .insert(BinaryOrbitPath);
+}
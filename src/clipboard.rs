@@ -0,0 +1,61 @@
+//! Browser clipboard of the web build, where egui's `copied_text` doesn't reach the system
+//! clipboard. `navigator.clipboard` is asynchronous, the text read arrives in the
+//! `ClipboardInbox` a few frames after `request_text`. The API is still unstable in web-sys,
+//! `.cargo/config.toml` builds with `--cfg=web_sys_unstable_apis`.
+use bevy::prelude::*;
+use std::sync::{Arc, Mutex};
+use wasm_bindgen::JsValue;
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+
+/// Text read from the clipboard, waiting to be taken by the next frame
+#[derive(Default, Clone)]
+pub struct ClipboardInbox(Arc<Mutex<Option<String>>>);
+
+impl ClipboardInbox {
+    pub fn take(&self) -> Option<String> {
+        self.0.lock().unwrap().take()
+    }
+}
+
+fn clipboard() -> Option<web_sys::Clipboard> {
+    web_sys::window()?.navigator().clipboard()
+}
+
+/// Waits for the clipboard promise, `None` after logging when it's rejected
+async fn settle(promise: js_sys::Promise, action: &'static str) -> Option<JsValue> {
+    match JsFuture::from(promise).await {
+        Ok(value) => Some(value),
+        Err(err) => {
+            warn!("{} the clipboard failed: {:?}", action, err);
+            None
+        }
+    }
+}
+
+pub fn write_text(text: &str) {
+    match clipboard() {
+        Some(clipboard) => {
+            let promise = clipboard.write_text(text);
+            spawn_local(async move {
+                settle(promise, "Writing to").await;
+            });
+        }
+        None => warn!("The browser doesn't provide a clipboard"),
+    }
+}
+
+pub fn request_text(inbox: &ClipboardInbox) {
+    let promise = match clipboard() {
+        Some(clipboard) => clipboard.read_text(),
+        None => {
+            warn!("The browser doesn't provide a clipboard");
+            return;
+        }
+    };
+    let inbox = inbox.clone();
+    spawn_local(async move {
+        if let Some(text) = settle(promise, "Reading").await {
+            *inbox.0.lock().unwrap() = text.as_string();
+        }
+    });
+}
@@ -0,0 +1,62 @@
+use crate::layers;
+use crate::pancam::PanCam;
+use crate::{Planet, Stats};
+use bevy::{prelude::*, render::camera::OrthographicProjection};
+use bevy_prototype_lyon::prelude::*;
+
+/// Crosshair markers at the barycenter of the system and at every sun, they keep
+/// their size on screen regardless of zoom.
+
+#[derive(Default)]
+pub struct CrosshairsPlugin;
+
+impl Plugin for CrosshairsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(draw_crosshairs);
+    }
+}
+
+/// Half of the crosshair width in pixels
+const CROSSHAIR_SIZE: f32 = 12.0;
+
+#[derive(Component)]
+struct Crosshair;
+
+fn draw_crosshairs(
+    mut commands: Commands,
+    stats: Res<Stats>,
+    planets: Query<(&Planet, &Transform)>,
+    camera: Query<&OrthographicProjection, With<PanCam>>,
+    crosshairs: Query<Entity, With<Crosshair>>,
+) {
+    for entity in crosshairs.iter() {
+        commands.entity(entity).despawn();
+    }
+    if !stats.draw_crosshairs {
+        return;
+    }
+    let pixel = match camera.iter().next() {
+        Some(projection) => projection.scale,
+        None => return,
+    };
+
+    let mut spawn_crosshair = |position: Vec2, color: Color| {
+        let size = CROSSHAIR_SIZE * pixel;
+        let mut builder = PathBuilder::new();
+        builder.move_to(Vec2::new(-size, 0.0));
+        builder.line_to(Vec2::new(size, 0.0));
+        builder.move_to(Vec2::new(0.0, -size));
+        builder.line_to(Vec2::new(0.0, size));
+        commands
+            .spawn_bundle(GeometryBuilder::build_as(
+                &builder.build(),
+                DrawMode::Stroke(StrokeMode::new(color, pixel)),
+                Transform::from_xyz(position.x, position.y, layers::MARKERS),
+            ))
+            .insert(Crosshair);
+    };
+    spawn_crosshair(stats.barycenter, Color::WHITE);
+    for (_, transform) in planets.iter().filter(|(planet, _)| planet.is_sun) {
+        spawn_crosshair(transform.translation.truncate(), Color::ORANGE);
+    }
+}
@@ -0,0 +1,103 @@
+use crate::{Planet, Settings, SimulationLabel, Stats, Velocity};
+use bevy::prelude::*;
+use nbody::physics::{self, Body};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// Appends a CSV line with the conserved quantities of the system every few simulation
+/// steps, so long runs can be plotted with external tools. Only in the desktop build.
+
+#[derive(Default)]
+pub struct DiagnosticsLogPlugin;
+
+impl Plugin for DiagnosticsLogPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(log_diagnostics.after(SimulationLabel::Gravity));
+    }
+}
+
+/// Lines written between flushes, a crash loses at most this many samples
+const FLUSH_EVERY: usize = 50;
+
+#[derive(Default)]
+struct LogFile {
+    writer: Option<BufWriter<File>>,
+    path: String,
+    unflushed: usize,
+}
+
+impl LogFile {
+    fn open(&mut self, path: &str) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(
+            writer,
+            "sim_time,n_objects,total_energy,total_momentum,barycenter_x,barycenter_y"
+        )?;
+        self.writer = Some(writer);
+        self.path = path.to_string();
+        self.unflushed = 0;
+        Ok(())
+    }
+}
+
+fn log_diagnostics(
+    mut settings: ResMut<Settings>,
+    stats: Res<Stats>,
+    planets: Query<(&Planet, &Velocity, &Transform)>,
+    mut log: Local<LogFile>,
+) {
+    if !settings.log_diagnostics {
+        // dropping the writer flushes it
+        log.writer = None;
+        return;
+    }
+    // the path is only read when logging is switched on, not while it is being typed
+    if log.writer.is_none() {
+        if let Err(err) = log.open(&settings.log_path) {
+            error!("can't write diagnostics to {}: {}", settings.log_path, err);
+            settings.log_diagnostics = false;
+            return;
+        }
+    }
+    if stats.frame_number % settings.log_interval.max(1) != 0 {
+        return;
+    }
+
+    let bodies: Vec<Body> = planets
+        .iter()
+        .map(|(planet, velocity, transform)| Body {
+            position: transform.translation.truncate(),
+            velocity: velocity.0,
+            mass: planet.mass(),
+            radius: planet.radius,
+            fixed: false,
+            is_sun: planet.is_sun,
+        })
+        .collect();
+    let totals = physics::system_totals(&bodies);
+    let energy = physics::total_energy(&bodies, settings.g);
+    let line = format!(
+        "{},{},{},{},{},{}",
+        stats.sim_time,
+        bodies.len(),
+        energy,
+        totals.momentum.length(),
+        totals.barycenter.x,
+        totals.barycenter.y
+    );
+
+    let log = &mut *log;
+    if let Some(writer) = log.writer.as_mut() {
+        let mut result = writeln!(writer, "{}", line);
+        log.unflushed += 1;
+        if result.is_ok() && log.unflushed >= FLUSH_EVERY {
+            log.unflushed = 0;
+            result = writer.flush();
+        }
+        if let Err(err) = result {
+            error!("can't write diagnostics to {}: {}", log.path, err);
+            log.writer = None;
+            settings.log_diagnostics = false;
+        }
+    }
+}
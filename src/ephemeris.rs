@@ -0,0 +1,86 @@
+//! Loading of real bodies from a JSON ephemeris, see README for the units.
+use bevy::math::Vec2;
+use serde::Deserialize;
+use std::f32::consts::PI;
+
+/// The Sun and the eight planets, bundled so the web build can use it as well
+pub const SOLAR_SYSTEM: &str = include_str!("../assets/ephemeris/solar_system.json");
+
+/// Gravitational constant in AU³ / (solar mass · year²)
+const G_ASTRONOMICAL: f32 = 4.0 * PI * PI;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct EphemerisBody {
+    pub name: String,
+    /// Solar masses
+    pub mass: f32,
+    /// Astronomical units
+    pub position: [f32; 2],
+    /// Astronomical units per year
+    pub velocity: [f32; 2],
+    /// Radius in simulation units, used as is because real radii are far too small to see
+    pub radius: f32,
+    /// Linear RGB in 0..1
+    pub color: [f32; 3],
+    #[serde(default)]
+    pub is_sun: bool,
+}
+
+/// Conversion from astronomical units to simulation units.
+pub struct Scaling {
+    /// Simulation units per astronomical unit
+    pub distance: f32,
+    /// Simulation mass units per solar mass
+    pub mass: f32,
+    /// Gravitational constant of the simulation
+    pub g: f32,
+}
+
+impl Scaling {
+    /// Velocities are scaled so that orbits keep their shape under the simulation `g`:
+    /// v_sim = v · sqrt(g · mass_scale / (G · distance_scale)).
+    /// With repulsive gravity there are no orbits to keep so the bodies start at rest.
+    fn velocity(&self) -> f32 {
+        (self.g * self.mass / (G_ASTRONOMICAL * self.distance))
+            .max(0.0)
+            .sqrt()
+    }
+
+    /// Years of real motion per simulation time unit, a body covers `distance` units
+    /// for every AU while moving `velocity` times faster than in AU per year.
+    pub fn years_per_time_unit(&self) -> f32 {
+        self.velocity() / self.distance
+    }
+}
+
+impl EphemerisBody {
+    pub fn scaled_position(&self, scaling: &Scaling) -> Vec2 {
+        Vec2::from(self.position) * scaling.distance
+    }
+
+    pub fn scaled_velocity(&self, scaling: &Scaling) -> Vec2 {
+        Vec2::from(self.velocity) * scaling.velocity()
+    }
+
+    pub fn scaled_mass(&self, scaling: &Scaling) -> f32 {
+        self.mass * scaling.mass
+    }
+}
+
+pub fn parse(json: &str) -> Result<Vec<EphemerisBody>, String> {
+    serde_json::from_str(json).map_err(|err| format!("invalid ephemeris: {}", err))
+}
+
+/// Reads the ephemeris from `path`, an empty path means the bundled solar system.
+/// Files can't be read in the browser so the web build always uses the bundled one.
+pub fn load(path: &str) -> Result<Vec<EphemerisBody>, String> {
+    #[cfg(not(target_arch = "wasm32"))]
+    if !path.is_empty() {
+        let json =
+            std::fs::read_to_string(path).map_err(|err| format!("can't read {}: {}", path, err))?;
+        return parse(&json);
+    }
+    #[cfg(target_arch = "wasm32")]
+    let _ = path;
+    parse(SOLAR_SYSTEM)
+}
@@ -0,0 +1,85 @@
+use crate::layers;
+use crate::pancam::PanCam;
+use crate::{largest_sun, Planet, Settings, Stats, Velocity};
+use bevy::{prelude::*, render::camera::OrthographicProjection};
+use bevy_prototype_lyon::prelude::*;
+use nbody::physics;
+
+/// Arrows pointing where the planets faster than the escape speed of the largest sun
+/// are heading, so the ones about to leave the system stand out.
+
+#[derive(Default)]
+pub struct EscapePlugin;
+
+impl Plugin for EscapePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(draw_escape_arrows);
+    }
+}
+
+/// Length of the arrows in pixels
+const ARROW_LENGTH: f32 = 30.0;
+
+#[derive(Component)]
+struct EscapeArrows;
+
+fn draw_escape_arrows(
+    mut commands: Commands,
+    stats: Res<Stats>,
+    settings: Res<Settings>,
+    planets: Query<(Entity, &Planet, &Transform, &Velocity)>,
+    camera: Query<&OrthographicProjection, With<PanCam>>,
+    drawn: Query<Entity, With<EscapeArrows>>,
+) {
+    for entity in drawn.iter() {
+        commands.entity(entity).despawn();
+    }
+    if !stats.escape_arrows {
+        return;
+    }
+    let sun = largest_sun(
+        planets
+            .iter()
+            .map(|(entity, planet, transform, velocity)| (planet, (entity, transform, velocity))),
+    );
+    let (sun, (sun_entity, sun_transform, sun_velocity)) = match sun {
+        Some(sun) => sun,
+        None => return,
+    };
+    let pixel = match camera.iter().next() {
+        Some(projection) => projection.scale,
+        None => return,
+    };
+
+    let mu = settings.g * sun.mass();
+    let sun_position = sun_transform.translation.truncate();
+    let mut builder = PathBuilder::new();
+    for (entity, planet, transform, velocity) in planets.iter() {
+        let position = transform.translation.truncate();
+        let relative_velocity = velocity.0 - sun_velocity.0;
+        let distance = position.distance(sun_position);
+        if entity == sun_entity
+            || distance == 0.0
+            || relative_velocity.length() <= physics::escape_speed(mu, distance)
+        {
+            continue;
+        }
+        let direction = relative_velocity.normalize();
+        let start = position + direction * planet.radius;
+        let end = start + direction * ARROW_LENGTH * pixel;
+        let back = -direction * ARROW_LENGTH * pixel / 3.0;
+        builder.move_to(start);
+        builder.line_to(end);
+        builder.move_to(end + back + back.perp() / 2.0);
+        builder.line_to(end);
+        builder.line_to(end + back - back.perp() / 2.0);
+    }
+    commands
+        .spawn_bundle(GeometryBuilder::build_as(
+            &builder.build(),
+            // one pixel wide regardless of the zoom
+            DrawMode::Stroke(StrokeMode::new(Color::ORANGE_RED, pixel)),
+            Transform::from_xyz(0.0, 0.0, layers::MARKERS),
+        ))
+        .insert(EscapeArrows);
+}
@@ -0,0 +1,75 @@
+use crate::quads::{fill_quads, quads_mesh, Quads};
+use crate::{trace_alpha, trace_layer, Stats, Trace};
+use bevy::prelude::*;
+use bevy::sprite::Mesh2dHandle;
+
+/// Glowing traces of `Stats.glow_traces`. Their points have no sprite, they are drawn as
+/// squares of a single mesh with additive blending, so overlapping traces add up towards
+/// white instead of covering each other. The mesh sits on the layer of the other traces.
+
+#[derive(Default)]
+pub struct GlowPlugin;
+
+impl Plugin for GlowPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(spawn_glow_cloud)
+            .add_system(draw_glow);
+    }
+}
+
+/// Trace point drawn as a glow
+#[derive(Component)]
+pub struct Glow {
+    /// Color before the fade of the trace
+    pub color: Color,
+    pub half_size: f32,
+}
+
+#[derive(Component)]
+struct GlowCloud;
+
+fn spawn_glow_cloud(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    commands.spawn_bundle((
+        Quads { additive: true },
+        GlowCloud,
+        Mesh2dHandle(meshes.add(quads_mesh())),
+        Transform::default(),
+        GlobalTransform::default(),
+        Visibility { is_visible: false },
+        ComputedVisibility::default(),
+    ));
+}
+
+fn draw_glow(
+    stats: Res<Stats>,
+    time: Res<Time>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    glows: Query<(&Glow, &Trace, &Transform), Without<GlowCloud>>,
+    mut cloud: Query<(&Mesh2dHandle, &mut Transform, &mut Visibility), With<GlowCloud>>,
+) {
+    let (mesh, mut transform, mut visibility) = match cloud.get_single_mut() {
+        Ok(cloud) => cloud,
+        Err(_) => return,
+    };
+    // there is nothing to draw in an empty mesh
+    let visible = !glows.is_empty();
+    if visibility.is_visible != visible {
+        visibility.is_visible = visible;
+    }
+    if !visible {
+        return;
+    }
+    let layer = trace_layer(&stats);
+    if transform.translation.z != layer {
+        transform.translation.z = layer;
+    }
+    if let Some(mesh) = meshes.get_mut(&mesh.0) {
+        let now = time.seconds_since_startup();
+        let quads = glows.iter().map(|(glow, trace, transform)| {
+            let mut color = glow.color;
+            color.set_a(trace_alpha(&stats, trace, now));
+            (transform.translation.truncate(), glow.half_size, color)
+        });
+        fill_quads(mesh, quads);
+    }
+}
@@ -0,0 +1,161 @@
+use crate::layers;
+use crate::pancam::PanCam;
+use crate::Stats;
+use bevy::{prelude::*, render::camera::OrthographicProjection};
+use bevy_egui::{egui, EguiContext};
+use bevy_prototype_lyon::prelude::*;
+
+/// Draws a world-space grid with spacing adapted to the current zoom level
+/// and labels the grid lines along the bottom and left edges of the window.
+
+#[derive(Default)]
+pub struct GridPlugin;
+
+impl Plugin for GridPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(draw_grid).add_system(grid_labels);
+    }
+}
+
+/// Approximate number of grid lines across the shorter side of the view
+const LINES_PER_VIEW: f32 = 8.0;
+
+#[derive(Component)]
+struct GridLines;
+
+/// Visible part of the world, rebuilt from the camera every frame.
+#[derive(Clone, Copy, PartialEq)]
+struct GridView {
+    min: Vec2,
+    max: Vec2,
+    spacing: f32,
+    scale: f32,
+}
+
+impl GridView {
+    fn from_camera(transform: &Transform, projection: &OrthographicProjection) -> Self {
+        let center = transform.translation.truncate();
+        // the camera is stretched vertically when the view is tilted
+        let scale = projection.scale * transform.scale.truncate();
+        let min = center + Vec2::new(projection.left, projection.bottom) * scale;
+        let max = center + Vec2::new(projection.right, projection.top) * scale;
+        Self {
+            min,
+            max,
+            spacing: grid_spacing((max - min).min_element()),
+            scale: projection.scale,
+        }
+    }
+
+    /// World coordinates of the grid lines crossing the `min..max` range
+    fn lines(&self, min: f32, max: f32) -> impl Iterator<Item = f32> {
+        let spacing = self.spacing;
+        let first = (min / spacing).ceil() as i64;
+        let last = (max / spacing).floor() as i64;
+        (first..=last).map(move |i| i as f32 * spacing)
+    }
+}
+
+/// Rounds the spacing to 1, 2 or 5 times a power of ten so the labels stay readable.
+fn grid_spacing(view_size: f32) -> f32 {
+    let raw = view_size / LINES_PER_VIEW;
+    let magnitude = 10f32.powf(raw.log10().floor());
+    let residual = raw / magnitude;
+    let step = if residual < 2.0 {
+        1.0
+    } else if residual < 5.0 {
+        2.0
+    } else {
+        5.0
+    };
+    step * magnitude
+}
+
+fn draw_grid(
+    mut commands: Commands,
+    stats: Res<Stats>,
+    camera: Query<(&Transform, &OrthographicProjection), With<PanCam>>,
+    grid: Query<Entity, With<GridLines>>,
+    mut last_view: Local<Option<GridView>>,
+) {
+    let view = match camera.iter().next() {
+        Some((transform, projection)) if stats.draw_grid => {
+            Some(GridView::from_camera(transform, projection))
+        }
+        _ => None,
+    };
+    if *last_view == view {
+        return;
+    }
+    *last_view = view;
+
+    for entity in grid.iter() {
+        commands.entity(entity).despawn();
+    }
+    let view = match view {
+        Some(view) => view,
+        None => return,
+    };
+
+    let mut builder = PathBuilder::new();
+    for x in view.lines(view.min.x, view.max.x) {
+        builder.move_to(Vec2::new(x, view.min.y));
+        builder.line_to(Vec2::new(x, view.max.y));
+    }
+    for y in view.lines(view.min.y, view.max.y) {
+        builder.move_to(Vec2::new(view.min.x, y));
+        builder.line_to(Vec2::new(view.max.x, y));
+    }
+    commands
+        .spawn_bundle(GeometryBuilder::build_as(
+            &builder.build(),
+            // keep the lines one pixel wide regardless of the zoom
+            DrawMode::Stroke(StrokeMode::new(Color::rgb(0.15, 0.15, 0.15), view.scale)),
+            Transform::from_xyz(0.0, 0.0, layers::GRID),
+        ))
+        .insert(GridLines);
+}
+
+fn grid_labels(
+    mut egui_context: ResMut<EguiContext>,
+    stats: Res<Stats>,
+    windows: Res<Windows>,
+    camera: Query<(&Transform, &OrthographicProjection), With<PanCam>>,
+) {
+    if !stats.draw_grid {
+        return;
+    }
+    let (transform, projection) = match camera.iter().next() {
+        Some(camera) => camera,
+        None => return,
+    };
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let view = GridView::from_camera(transform, projection);
+    let center = transform.translation.truncate();
+    let to_screen = |world: Vec2| {
+        let screen = (world - center) / (projection.scale * transform.scale.truncate());
+        egui::pos2(
+            window.width() / 2.0 + screen.x,
+            window.height() / 2.0 - screen.y,
+        )
+    };
+
+    let ctx = egui_context.ctx_mut();
+    for x in view.lines(view.min.x, view.max.x) {
+        let pos = to_screen(Vec2::new(x, 0.0));
+        egui::Area::new(("grid_label_x", x.to_bits()))
+            .fixed_pos(egui::pos2(pos.x + 2.0, window.height() - 20.0))
+            .interactable(false)
+            .show(ctx, |ui| ui.label(format!("{}", x)));
+    }
+    for y in view.lines(view.min.y, view.max.y) {
+        let pos = to_screen(Vec2::new(0.0, y));
+        egui::Area::new(("grid_label_y", y.to_bits()))
+            .fixed_pos(egui::pos2(2.0, pos.y + 2.0))
+            .interactable(false)
+            .show(ctx, |ui| ui.label(format!("{}", y)));
+    }
+}
@@ -0,0 +1,80 @@
+use crate::layers;
+use crate::pancam::PanCam;
+use crate::{largest_sun, Planet, Selected, Stats};
+use bevy::{prelude::*, render::camera::OrthographicProjection};
+use bevy_prototype_lyon::prelude::*;
+use nbody::physics;
+use std::f32::consts::PI;
+
+/// Dashed circle around the selected planet showing its Hill sphere relative to the
+/// largest sun, the region where its own gravity dominates and moons can stay.
+
+#[derive(Default)]
+pub struct HillSpherePlugin;
+
+impl Plugin for HillSpherePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(draw_hill_sphere);
+    }
+}
+
+/// Number of dashes and gaps around the circle
+const HILL_SPHERE_SEGMENTS: usize = 64;
+
+#[derive(Component)]
+struct HillSphere;
+
+fn draw_hill_sphere(
+    mut commands: Commands,
+    stats: Res<Stats>,
+    selected: Query<(Entity, &Planet, &Transform), With<Selected>>,
+    planets: Query<(Entity, &Planet, &Transform)>,
+    camera: Query<&OrthographicProjection, With<PanCam>>,
+    drawn: Query<Entity, With<HillSphere>>,
+) {
+    for entity in drawn.iter() {
+        commands.entity(entity).despawn();
+    }
+    if !stats.draw_hill_sphere {
+        return;
+    }
+    let (selected_entity, planet, transform) = match selected.iter().next() {
+        Some(selected) => selected,
+        None => return,
+    };
+    let sun = largest_sun(
+        planets
+            .iter()
+            .filter(|(entity, _, _)| *entity != selected_entity)
+            .map(|(_, planet, transform)| (planet, transform)),
+    );
+    let (sun, sun_transform) = match sun {
+        Some(sun) => sun,
+        None => return,
+    };
+    let pixel = match camera.iter().next() {
+        Some(projection) => projection.scale,
+        None => return,
+    };
+
+    let position = transform.translation.truncate();
+    let distance = position.distance(sun_transform.translation.truncate());
+    let radius = physics::hill_radius(distance, planet.mass(), sun.mass());
+    let mut builder = PathBuilder::new();
+    for i in (0..HILL_SPHERE_SEGMENTS).step_by(2) {
+        let point = |i: usize| {
+            let angle = i as f32 / HILL_SPHERE_SEGMENTS as f32 * 2.0 * PI;
+            Vec2::new(angle.cos(), angle.sin()) * radius
+        };
+        builder.move_to(point(i));
+        builder.line_to(point(i + 1));
+    }
+    commands
+        .spawn_bundle(GeometryBuilder::build_as(
+            &builder.build(),
+            // one pixel wide regardless of the zoom
+            DrawMode::Stroke(StrokeMode::new(Color::rgba(0.5, 0.8, 1.0, 0.7), pixel)),
+            Transform::from_xyz(position.x, position.y, layers::MARKERS),
+        ))
+        .insert(HillSphere);
+}
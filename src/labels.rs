@@ -0,0 +1,86 @@
+use crate::pancam::PanCam;
+use crate::{Planet, Selected, Stats};
+use bevy::{prelude::*, render::camera::OrthographicProjection};
+use bevy_egui::{egui, EguiContext};
+
+/// Names given to the planets in the "Selected planet" section, drawn next to the
+/// planets when `Stats.show_labels` is on. The ephemeris names its bodies as well.
+
+#[derive(Default)]
+pub struct LabelsPlugin;
+
+impl Plugin for LabelsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(name_selected_planet).add_system(draw_labels);
+    }
+}
+
+/// Keeps `Stats.selected_name`, edited in the UI, and the `Name` of the selected planet
+/// in sync. A newly selected planet fills in its name, afterwards the edits are applied
+/// to it and an empty name removes it.
+fn name_selected_planet(
+    mut commands: Commands,
+    mut stats: ResMut<Stats>,
+    selected: Query<(Entity, Option<&Name>), With<Selected>>,
+    mut last_selected: Local<Option<Entity>>,
+) {
+    let (entity, name) = match selected.iter().next() {
+        Some((entity, name)) => (Some(entity), name),
+        None => (None, None),
+    };
+    let current = name.map_or("", |name| name.as_str());
+    if entity != *last_selected {
+        *last_selected = entity;
+        stats.selected_name = current.to_string();
+        return;
+    }
+    let entity = match entity {
+        Some(entity) => entity,
+        None => return,
+    };
+    if stats.selected_name != current {
+        if stats.selected_name.is_empty() {
+            commands.entity(entity).remove::<Name>();
+        } else {
+            commands
+                .entity(entity)
+                .insert(Name::new(stats.selected_name.clone()));
+        }
+    }
+}
+
+fn draw_labels(
+    mut egui_context: ResMut<EguiContext>,
+    stats: Res<Stats>,
+    windows: Res<Windows>,
+    planets: Query<(Entity, &Name, &Planet, &Transform), Without<PanCam>>,
+    camera: Query<(&Transform, &OrthographicProjection), With<PanCam>>,
+) {
+    if !stats.show_labels {
+        return;
+    }
+    let (camera_transform, projection) = match camera.iter().next() {
+        Some(camera) => camera,
+        None => return,
+    };
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let center = camera_transform.translation.truncate();
+    let scale = projection.scale * camera_transform.scale.truncate();
+
+    let ctx = egui_context.ctx_mut();
+    for (entity, name, planet, transform) in planets.iter() {
+        let screen = (transform.translation.truncate() - center) / scale;
+        // right of the planet, wherever it is on the screen
+        let pos = egui::pos2(
+            window.width() / 2.0 + screen.x + planet.radius / scale.x + 4.0,
+            window.height() / 2.0 - screen.y - 8.0,
+        );
+        egui::Area::new(("planet_label", entity.id()))
+            .fixed_pos(pos)
+            .interactable(false)
+            .show(ctx, |ui| ui.label(name.as_str()));
+    }
+}
@@ -0,0 +1,18 @@
+//! Depth (`translation.z`) of everything drawn in the world, higher layers are drawn on top.
+
+/// Background star-field
+pub const STARS: f32 = 0.0;
+/// Grid lines and the arena walls
+pub const GRID: f32 = 0.5;
+/// Planet traces in their default place below everything else
+pub const TRACES: f32 = 1.0;
+/// Lensing rings and the launch preview, right under the planets
+pub const OVERLAYS: f32 = 9.0;
+pub const PLANETS: f32 = 10.0;
+/// Largest shift of a planet from `PLANETS` with `Settings.depth_scatter`, small enough to stay
+/// between the neighbouring layers
+pub const PLANET_DEPTH: f32 = 0.4;
+/// Planet traces when they are drawn over the planets
+pub const TRACES_OVER_PLANETS: f32 = 10.5;
+/// Merge flashes and crosshairs
+pub const MARKERS: f32 = 11.0;
@@ -0,0 +1,65 @@
+use crate::layers;
+use crate::{Planet, Settings};
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+
+/// Cosmetic imitation of gravitational lensing: faint concentric rings around the most
+/// massive body, their size grows with its mass. Nothing here affects the simulation.
+
+#[derive(Default)]
+pub struct LensingPlugin;
+
+impl Plugin for LensingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(draw_lensing);
+    }
+}
+
+const N_RINGS: usize = 4;
+/// Spacing of the rings as a fraction of the innermost one
+const RING_SPACING: f32 = 0.15;
+
+#[derive(Component)]
+struct LensingRing;
+
+fn draw_lensing(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    planets: Query<(&Planet, &Transform)>,
+    rings: Query<Entity, With<LensingRing>>,
+) {
+    for entity in rings.iter() {
+        commands.entity(entity).despawn();
+    }
+    if !settings.lensing {
+        return;
+    }
+    let largest = planets
+        .iter()
+        .max_by(|(a, _), (b, _)| a.mass().total_cmp(&b.mass()));
+    let (planet, transform) = match largest {
+        Some(largest) => largest,
+        None => return,
+    };
+
+    // like the Einstein radius the rings grow with the square root of the mass
+    let einstein_radius = planet.radius + settings.lensing_strength * planet.mass().sqrt();
+    for i in 0..N_RINGS {
+        let shape = shapes::Circle {
+            radius: einstein_radius * (1.0 + RING_SPACING * i as f32),
+            center: Default::default(),
+        };
+        let alpha = 0.25 / (i + 1) as f32;
+        commands
+            .spawn_bundle(GeometryBuilder::build_as(
+                &shape,
+                DrawMode::Stroke(StrokeMode::new(Color::rgba(0.6, 0.7, 1.0, alpha), 1.0)),
+                Transform::from_xyz(
+                    transform.translation.x,
+                    transform.translation.y,
+                    layers::OVERLAYS,
+                ),
+            ))
+            .insert(LensingRing);
+    }
+}
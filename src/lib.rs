@@ -1 +1 @@
-
+pub mod physics;
@@ -0,0 +1,122 @@
+use crate::{MergeLog, MergeRecord};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{Settings, SimulationLabel};
+#[cfg(not(target_arch = "wasm32"))]
+use bevy::prelude::*;
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs::File;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::{BufWriter, Write};
+
+/// CSV log with a line for every merge: when it happened, the masses of both parents and
+/// of the merged planet, and the impact speed. The desktop build appends the merges to a
+/// file as they happen, the web build downloads the merges since the last reset at once.
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default)]
+pub struct MergeLogPlugin;
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Plugin for MergeLogPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(write_merges.after(SimulationLabel::Gravity));
+    }
+}
+
+const HEADER: &str = "sim_time,survivor_mass,absorbed_mass,merged_mass,impact_speed";
+
+fn csv_line(record: &MergeRecord) -> String {
+    format!(
+        "{},{},{},{},{}",
+        record.sim_time,
+        record.survivor_mass,
+        record.absorbed_mass,
+        record.merged_mass,
+        record.impact_speed
+    )
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default)]
+struct LogFile {
+    writer: Option<BufWriter<File>>,
+    path: String,
+    /// Number of records of the `MergeLog` already in the file
+    written: usize,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl LogFile {
+    fn open(&mut self, path: &str) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "{}", HEADER)?;
+        self.writer = Some(writer);
+        self.path = path.to_string();
+        // the merges since the last reset go first
+        self.written = 0;
+        Ok(())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_merges(mut settings: ResMut<Settings>, merge_log: Res<MergeLog>, mut log: Local<LogFile>) {
+    if !settings.log_merges {
+        // dropping the writer flushes it
+        log.writer = None;
+        return;
+    }
+    if log.writer.is_none() {
+        if let Err(err) = log.open(&settings.merge_log_path) {
+            error!("can't write merges to {}: {}", settings.merge_log_path, err);
+            settings.log_merges = false;
+            return;
+        }
+    }
+    // the merge log starts over on reset, the file goes on
+    if log.written > merge_log.0.len() {
+        log.written = 0;
+    }
+    if log.written == merge_log.0.len() {
+        return;
+    }
+
+    let log = &mut *log;
+    if let Some(writer) = log.writer.as_mut() {
+        let result = merge_log.0[log.written..]
+            .iter()
+            .try_for_each(|record| writeln!(writer, "{}", csv_line(record)))
+            // merges are rare, flush them right away
+            .and_then(|_| writer.flush());
+        log.written = merge_log.0.len();
+        if let Err(err) = result {
+            error!("can't write merges to {}: {}", log.path, err);
+            log.writer = None;
+            settings.log_merges = false;
+        }
+    }
+}
+
+/// Downloads the merges since the last reset as a CSV file.
+#[cfg(target_arch = "wasm32")]
+pub fn download(merge_log: &MergeLog) -> Result<(), String> {
+    use wasm_bindgen::JsCast;
+
+    let mut csv = format!("{}\n", HEADER);
+    for record in merge_log.0.iter() {
+        csv.push_str(&csv_line(record));
+        csv.push('\n');
+    }
+    let url = format!(
+        "data:text/csv;charset=utf-8,{}",
+        String::from(js_sys::encode_uri_component(&csv))
+    );
+    let link = web_sys::window()
+        .and_then(|window| window.document())
+        .and_then(|document| document.create_element("a").ok())
+        .and_then(|link| link.dyn_into::<web_sys::HtmlAnchorElement>().ok())
+        .ok_or("can't create a link")?;
+    link.set_href(&url);
+    link.set_download("nbody-merges.csv");
+    link.click();
+    Ok(())
+}
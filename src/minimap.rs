@@ -0,0 +1,106 @@
+use crate::pancam::PanCam;
+use crate::{Planet, Stats};
+use bevy::{prelude::*, render::camera::OrthographicProjection};
+use bevy_egui::{egui, EguiContext};
+
+/// Small overview of the whole system in the bottom right corner of the window.
+/// Every planet is a dot, the part seen by the camera is outlined and clicking
+/// the minimap moves the camera there.
+
+#[derive(Default)]
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(draw_minimap);
+    }
+}
+
+/// Side of the minimap in pixels
+const MINIMAP_SIZE: f32 = 200.0;
+/// Empty space left around the planets, as a fraction of the minimap
+const MINIMAP_MARGIN: f32 = 0.05;
+
+fn draw_minimap(
+    mut egui_context: ResMut<EguiContext>,
+    mut stats: ResMut<Stats>,
+    planets: Query<(&Planet, &Transform), Without<PanCam>>,
+    mut camera: Query<(&mut Transform, &OrthographicProjection), With<PanCam>>,
+) {
+    if !stats.draw_minimap {
+        return;
+    }
+    let (mut camera_transform, projection) = match camera.iter_mut().next() {
+        Some(camera) => camera,
+        None => return,
+    };
+    let center = camera_transform.translation.truncate();
+    let scale = projection.scale * camera_transform.scale.truncate();
+    let view_min = center + Vec2::new(projection.left, projection.bottom) * scale;
+    let view_max = center + Vec2::new(projection.right, projection.top) * scale;
+
+    // fit all the planets and the camera view, keeping the aspect ratio
+    let (min, max) = planets
+        .iter()
+        .fold((view_min, view_max), |(min, max), (_, transform)| {
+            let position = transform.translation.truncate();
+            (min.min(position), max.max(position))
+        });
+    let extent = (max - min).max_element().max(1.0) * (1.0 + 2.0 * MINIMAP_MARGIN);
+    let middle = (min + max) / 2.0;
+    let world_min = middle - Vec2::splat(extent / 2.0);
+
+    egui::Area::new("minimap")
+        .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-10.0, -10.0))
+        .show(egui_context.ctx_mut(), |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                let (response, painter) = ui.allocate_painter(
+                    egui::vec2(MINIMAP_SIZE, MINIMAP_SIZE),
+                    egui::Sense::click_and_drag(),
+                );
+                let rect = response.rect;
+                // world y points up, screen y points down
+                let to_minimap = |world: Vec2| {
+                    let relative = (world - world_min) / extent;
+                    egui::pos2(
+                        rect.left() + relative.x * rect.width(),
+                        rect.bottom() - relative.y * rect.height(),
+                    )
+                };
+
+                for (planet, transform) in planets.iter() {
+                    let [r, g, b, _] = planet.color.as_rgba_f32();
+                    let color = egui::Color32::from_rgb(
+                        (r * 255.0) as u8,
+                        (g * 255.0) as u8,
+                        (b * 255.0) as u8,
+                    );
+                    let radius = (planet.radius / extent * MINIMAP_SIZE).max(1.0);
+                    painter.circle_filled(
+                        to_minimap(transform.translation.truncate()),
+                        radius,
+                        color,
+                    );
+                }
+                painter.rect_stroke(
+                    egui::Rect::from_two_pos(to_minimap(view_min), to_minimap(view_max)),
+                    0.0,
+                    egui::Stroke::new(1.0, egui::Color32::WHITE),
+                );
+
+                if let Some(pos) = response.interact_pointer_pos() {
+                    if response.clicked() || response.dragged() {
+                        let relative = egui::vec2(
+                            (pos.x - rect.left()) / rect.width(),
+                            (rect.bottom() - pos.y) / rect.height(),
+                        );
+                        let target = world_min + Vec2::new(relative.x, relative.y) * extent;
+                        camera_transform.translation.x = target.x;
+                        camera_transform.translation.y = target.y;
+                        // otherwise the camera jumps back to the largest planet
+                        stats.center_on_largest = false;
+                    }
+                }
+            });
+        });
+}
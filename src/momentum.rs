@@ -0,0 +1,64 @@
+use crate::layers;
+use crate::pancam::PanCam;
+use crate::{Settings, Stats};
+use bevy::{prelude::*, render::camera::OrthographicProjection};
+use bevy_prototype_lyon::prelude::*;
+
+/// Arrow from the barycenter along the total momentum of the system. A system left to
+/// itself keeps the arrow unchanged, so any change shows momentum being lost or gained.
+
+#[derive(Default)]
+pub struct MomentumPlugin;
+
+impl Plugin for MomentumPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(draw_momentum);
+    }
+}
+
+/// Length of the arrow head in pixels
+const ARROW_HEAD_SIZE: f32 = 10.0;
+
+#[derive(Component)]
+struct MomentumArrow;
+
+fn draw_momentum(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    stats: Res<Stats>,
+    camera: Query<&OrthographicProjection, With<PanCam>>,
+    drawn: Query<Entity, With<MomentumArrow>>,
+) {
+    for entity in drawn.iter() {
+        commands.entity(entity).despawn();
+    }
+    if !stats.draw_momentum {
+        return;
+    }
+    let pixel = match camera.iter().next() {
+        Some(projection) => projection.scale,
+        None => return,
+    };
+    let arrow = stats.momentum * settings.momentum_arrow_scale;
+    // no direction to point in
+    if arrow.length() < pixel {
+        return;
+    }
+
+    let head = ARROW_HEAD_SIZE * pixel;
+    let back = -arrow.normalize() * head;
+    let mut builder = PathBuilder::new();
+    builder.move_to(Vec2::ZERO);
+    builder.line_to(arrow);
+    builder.move_to(arrow + back + back.perp() / 2.0);
+    builder.line_to(arrow);
+    builder.line_to(arrow + back - back.perp() / 2.0);
+    commands
+        .spawn_bundle(GeometryBuilder::build_as(
+            &builder.build(),
+            // two pixels wide regardless of the zoom
+            DrawMode::Stroke(StrokeMode::new(Color::YELLOW, 2.0 * pixel)),
+            Transform::from_xyz(stats.barycenter.x, stats.barycenter.y, layers::MARKERS),
+        ))
+        .insert(MomentumArrow);
+}
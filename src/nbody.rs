@@ -1,14 +1,66 @@
+mod binary;
+#[cfg(target_arch = "wasm32")]
+mod clipboard;
+mod crosshairs;
+#[cfg(not(target_arch = "wasm32"))]
+mod diagnostics_log;
+mod ephemeris;
+mod escape;
+mod glow;
+mod grid;
+mod hill_sphere;
+mod labels;
+mod layers;
+mod lensing;
+mod merge_log;
+mod minimap;
+mod momentum;
 mod pancam;
+mod points;
+mod quads;
+mod replay;
+mod scene;
+mod screenshot;
+mod stars;
+#[cfg(test)]
+mod tests;
+#[cfg(target_arch = "wasm32")]
+mod url_params;
 
-use crate::pancam::{PanCam, PanCamPlugin};
-use bevy::diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin};
+use crate::binary::{BinaryOrbit, BinaryPlugin};
+use crate::crosshairs::CrosshairsPlugin;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::diagnostics_log::DiagnosticsLogPlugin;
+use crate::escape::EscapePlugin;
+use crate::glow::{Glow, GlowPlugin};
+use crate::grid::GridPlugin;
+use crate::hill_sphere::HillSpherePlugin;
+use crate::labels::LabelsPlugin;
+use crate::lensing::LensingPlugin;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::merge_log::MergeLogPlugin;
+use crate::minimap::MinimapPlugin;
+use crate::momentum::MomentumPlugin;
+use crate::pancam::{cursor_to_world, PanCam, PanCamPlugin};
+use crate::points::PointsPlugin;
+use crate::quads::QuadsPlugin;
+use crate::replay::{Replay, ReplayMode, ReplayPlugin};
+use crate::screenshot::Screenshot;
+use crate::screenshot::ScreenshotPlugin;
+use crate::stars::StarFieldPlugin;
+use bevy::diagnostic::{Diagnostics, EntityCountDiagnosticsPlugin, FrameTimeDiagnosticsPlugin};
 use bevy::prelude::*;
+use bevy::render::camera::OrthographicProjection;
 use bevy_egui::{egui, EguiContext, EguiPlugin};
 use bevy_fly_camera::{FlyCamera2d, FlyCameraPlugin};
 use bevy_prototype_lyon::prelude::*;
 use derive_more::Deref;
-use rand::Rng;
-use std::collections::{HashMap, HashSet};
+use nbody::physics::{
+    self, Body, ForceParams, Integrator, MergeDimension, MergePolicy, SofteningMode,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::f32::consts::PI;
 use wasm_bindgen::prelude::*;
 
@@ -23,47 +75,796 @@ struct Stats {
     frame_number: usize,
     n_objects: usize,
     center_on_largest: bool,
-    draw_traces: bool,
+    /// Keep the camera on the selected planet
+    follow_selected: bool,
+    /// Set from the UI, the camera starts moving to the selected planet on the next frame
+    focus_selected_pending: bool,
+    /// The planets stand still, "Step" advances them by a single step
+    paused: bool,
+    step_pending: bool,
+    /// First collision since the last `Reset` with `Settings.pause_on_collision`
+    paused_collision: Option<PausedCollision>,
+    /// Set with `Settings.center_on_collision`, the camera moves to `paused_collision`
+    focus_collision_pending: bool,
+    trace_mode: TraceMode,
+    trace_largest_n: usize,
+    trace_fade: TraceFade,
+    /// World distance a planet travels between two trace points, zero drops one every
+    /// `TRACE_INTERVAL_FRAMES` frames instead
+    trace_spacing: f32,
+    gray_traces: bool,
+    /// Draw the traces as large faint glows, blended additively so they brighten where they
+    /// overlap
+    glow_traces: bool,
+    /// Draw the traces on top of the planets instead of under them
+    traces_over_planets: bool,
+    merge_flash: bool,
+    draw_grid: bool,
+    draw_stars: bool,
+    /// Overview of the whole system in the corner of the window
+    draw_minimap: bool,
+    /// Mark the barycenter and the suns with crosshairs
+    draw_crosshairs: bool,
+    /// Circle around the selected planet where its gravity dominates the largest sun's
+    draw_hill_sphere: bool,
+    /// Arrow from the barycenter along the total momentum
+    draw_momentum: bool,
+    /// Draw the `Name` of the planets next to them
+    show_labels: bool,
+    /// Name of the selected planet as edited in the UI
+    selected_name: String,
     largest_position: Vec2,
+    barycenter: Vec2,
+    /// Total momentum, the sum of m·v
+    momentum: Vec2,
+    /// World position of the mouse cursor, `None` when it's outside the window
+    cursor_position: Option<Vec2>,
+    ejected_count: usize,
+    /// Merges since the last `Reset`
+    collision_count: usize,
+    /// Sum of the planet masses, only culling should change it
+    total_mass: f32,
+    angular_momentum: f32,
+    /// Angular momentum right after the last `Reset`, used to measure the drift
+    initial_angular_momentum: Option<f32>,
+    /// Total energy kept by `Settings.energy_correction` with the number of planets and `g`
+    /// it was measured with, merges, removals and a new `g` change the energy so it is
+    /// measured again. Kicks like stirring clear it.
+    reference_energy: Option<(f32, usize, f32)>,
+    /// Integration steps since the energy was last corrected
+    steps_since_correction: usize,
+    /// Two closest planets and the distance between their centers
+    closest_pair: Option<(Entity, Entity, f32)>,
+    /// Orbit of the `BinaryPartner` around the selected planet
+    binary_orbit: Option<BinaryOrbit>,
+    /// Number of times a planet reached a NaN or infinite state
+    nan_events: usize,
+    /// Wall clock time until which the time step changed with the keyboard is shown
+    time_step_notice_until: f64,
+    /// Scenario the planets on screen were built with, set on `Reset`
+    scenario: Option<Scenario>,
+    /// Set on `Reset`, the first integration step after it starts the integrator
+    integrator_start_pending: bool,
+    /// Simulated time since the last `Reset`, sum of the integration steps
+    sim_time: f64,
+    /// Scene code shown after "Copy scene" or pasted by the user
+    scene_code: String,
+    /// Set from the UI, the current planets are opened in a new window on the next frame
+    #[cfg(not(target_arch = "wasm32"))]
+    duplicate_pending: bool,
+    /// The settings side panel is collapsed to leave more room for the simulation
+    hide_settings_panel: bool,
+    /// Show the settings in a window which can be dragged around instead of the side panel
+    floating_settings_panel: bool,
+    /// The floating settings window can't be dragged and stays above the other windows
+    pin_settings_panel: bool,
+    /// Top left corner of the floating settings window, kept when the panel is switched
+    settings_window_pos: Option<egui::Pos2>,
+    /// Zero is opaque, higher values let the simulation show through the settings
+    settings_panel_transparency: f32,
+    /// Left click places an anchor instead of selecting a planet
+    place_anchors: bool,
+    /// Set on `Reset` when comparing integrators, the next step copies the scene
+    comparison_pending: bool,
+    /// Merges per simulated time unit, exponentially smoothed
+    accretion_rate: f32,
+    /// Decrease of the number of planets per simulated time unit, exponentially smoothed
+    body_decline_rate: f32,
+    /// Last values of `accretion_rate`, one per step
+    accretion_history: VecDeque<f32>,
+    /// Planets on elliptic and on escaping orbits around the largest sun
+    bound_count: usize,
+    unbound_count: usize,
+    /// Draw the planets escaping the largest sun in red
+    tint_unbound: bool,
+    /// Arrows along the velocity of the planets faster than the escape speed
+    escape_arrows: bool,
+    color_mode: ColorMode,
+    number_format: NumberFormat,
+}
+
+/// Which planets leave traces behind them.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum TraceMode {
+    None,
+    All,
+    /// Only the planet picked with a mouse click
+    Selected,
+    /// Only the `trace_largest_n` most massive planets
+    LargestN,
+}
+
+impl Default for TraceMode {
+    fn default() -> Self {
+        TraceMode::None
+    }
+}
+
+/// What the planet colors show.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum ColorMode {
+    /// Color given at spawn, possibly blended on merges
+    Original,
+    /// From blue for untouched planets to red for those with `MERGE_COLOR_SCALE` merges
+    MergeCount,
+    /// Glowing from dark red to white while hot after a collision
+    Temperature,
+}
+
+impl Default for ColorMode {
+    fn default() -> Self {
+        ColorMode::Original
+    }
+}
+
+/// Number of merges drawn in the reddest color with `ColorMode::MergeCount`
+const MERGE_COLOR_SCALE: u32 = 50;
+
+fn merge_count_color(merges: u32) -> Color {
+    // logarithmic so the first few merges are visible as well
+    let t = ((1 + merges) as f32).ln() / ((1 + MERGE_COLOR_SCALE) as f32).ln();
+    let t = t.min(1.0);
+    Color::rgb(0.2 + 0.8 * t, 0.4, 1.0 - 0.8 * t)
+}
+
+/// Temperature drawn in white with `ColorMode::Temperature`
+const HOT_TEMPERATURE: f32 = 3000.0;
+/// Collision energy needed to heat a unit of mass by one degree
+const HEAT_CAPACITY: f32 = 0.01;
+
+fn temperature_color(temperature: f32) -> Color {
+    // black body like: red first, then orange and yellow, white when hottest
+    let t = (temperature / HOT_TEMPERATURE).clamp(0.0, 1.0);
+    Color::rgb(
+        0.2 + 0.8 * (t * 3.0).min(1.0),
+        0.1 + 0.9 * (t * 3.0 - 1.0).clamp(0.0, 1.0),
+        0.1 + 0.9 * (t * 3.0 - 2.0).clamp(0.0, 1.0),
+    )
+}
+
+/// How the floating point readouts of the stats bar are printed.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum NumberFormat {
+    /// Two decimal places
+    Fixed,
+    Scientific,
+    /// Thousands shown with k, M, G, T suffixes
+    SiSuffix,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        NumberFormat::Fixed
+    }
+}
+
+fn format_number(value: f64, format: NumberFormat) -> String {
+    match format {
+        NumberFormat::Fixed => format!("{:.2}", value),
+        NumberFormat::Scientific => format!("{:.3e}", value),
+        NumberFormat::SiSuffix => {
+            const SUFFIXES: [&str; 5] = ["", "k", "M", "G", "T"];
+            let mut scaled = value;
+            let mut suffix = 0;
+            while scaled.abs() >= 1000.0 && suffix < SUFFIXES.len() - 1 {
+                scaled /= 1000.0;
+                suffix += 1;
+            }
+            format!("{:.2}{}", scaled, SUFFIXES[suffix])
+        }
+    }
+}
+
+/// How the trace alpha decreases with its age.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum TraceFade {
+    Off,
+    Linear,
+    Exponential,
+}
+
+impl Default for TraceFade {
+    fn default() -> Self {
+        TraceFade::Linear
+    }
+}
+
+/// Seconds a trace stays on screen
+const TRACE_LIFETIME: f64 = 10.0;
+/// Frames between two trace points when `Stats.trace_spacing` is zero
+const TRACE_INTERVAL_FRAMES: usize = 5;
+/// Longest trace lifetime with `Settings.speed_trails`, as a multiple of `TRACE_LIFETIME`
+const MAX_SPEED_TRAIL: f32 = 3.0;
+/// Decay rate of the exponential trace fade, alpha at the end of the lifetime is exp(-rate)
+const TRACE_FADE_RATE: f32 = 4.0;
+/// Side of a trace point in world units with `Stats.glow_traces`, plain points are 1
+const GLOW_SIZE: f32 = 4.0;
+/// Opacity of a fresh trace point with `Stats.glow_traces`
+const GLOW_OPACITY: f32 = 0.25;
+
+/// Seconds a merge flash stays on screen
+const FLASH_DURATION: f64 = 0.4;
+/// Final radius of a merge flash relative to the radius of the merged planet
+const FLASH_SIZE: f32 = 4.0;
+/// Seconds the absorbed planet takes to shrink into the merged one with `Settings.animate_merges`
+const MERGE_ANIMATION_DURATION: f64 = 0.3;
+
+/// How planets are drawn.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+enum RenderMode {
+    /// Every planet is a separate shape
+    Shapes,
+    /// All planets of the same color are drawn as squares in a single shape
+    Points,
+}
+
+/// How the masses of the generated planets are distributed.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+enum MassDistribution {
+    /// Radius and density are uniform in their ranges
+    Uniform,
+    /// Mass follows a power law between the lightest and heaviest planet the ranges allow,
+    /// the radius is derived from the mass and a uniform density
+    PowerLaw,
+}
+
+/// Colors given to the generated planets, kept in `Planet.color` as their own color.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+enum Palette {
+    White,
+    /// Hues cycled in the order the planets are created
+    Rainbow,
+    /// The perceptually uniform viridis map from dark purple to yellow
+    Viridis,
+    /// Random hues drawn from the seed
+    Random,
+}
+
+/// Samples of the viridis color map, evenly spaced between 0 and 1
+const VIRIDIS: [[f32; 3]; 5] = [
+    [0.267, 0.005, 0.329],
+    [0.229, 0.322, 0.546],
+    [0.128, 0.567, 0.551],
+    [0.369, 0.789, 0.383],
+    [0.993, 0.906, 0.144],
+];
+
+/// Color of the `index`-th of `count` planets, `rng` is only used by `Palette::Random`.
+fn palette_color(palette: Palette, index: usize, count: usize, rng: &mut impl Rng) -> Color {
+    match palette {
+        Palette::White => Color::WHITE,
+        // the hue wraps around, the last planet stops short of the first color
+        Palette::Rainbow => Color::hsl(index as f32 / count.max(1) as f32 * 360.0, 0.8, 0.6),
+        Palette::Viridis => {
+            let t = index as f32 / count.saturating_sub(1).max(1) as f32;
+            let position = t * (VIRIDIS.len() - 1) as f32;
+            let i = (position as usize).min(VIRIDIS.len() - 2);
+            let fraction = position - i as f32;
+            let mix = |c: usize| VIRIDIS[i][c] + (VIRIDIS[i + 1][c] - VIRIDIS[i][c]) * fraction;
+            Color::rgb(mix(0), mix(1), mix(2))
+        }
+        Palette::Random => Color::hsl(rng.gen::<f32>() * 360.0, 0.8, 0.6),
+    }
+}
+
+/// Outline of the planets in the `Shapes` render mode, collisions always use the radius.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+enum BodyShape {
+    Circle,
+    Square,
+    Star,
+}
+
+/// Weight of the newest step in the smoothed accretion and decline rates
+const RATE_SMOOTHING: f32 = 0.01;
+/// Number of steps shown in the accretion rate graph
+const ACCRETION_HISTORY: usize = 600;
+
+/// Part of the lighter planet's mass ejected as debris by a fast impact
+const DEBRIS_MASS_FRACTION: f32 = 0.2;
+/// Ejection speed of the debris as a fraction of the impact speed
+const DEBRIS_SPEED_FRACTION: f32 = 0.5;
+
+/// Number of the most recent merges listed in the UI
+const MERGE_LOG_SHOWN: usize = 20;
+
+/// Bounds of the time step slider and keyboard control
+const MIN_TIME_STEP: f32 = 1.0;
+const MAX_TIME_STEP: f32 = 1000.0;
+/// Seconds the time step readout stays on screen after changing it with the keyboard
+const TIME_STEP_NOTICE_DURATION: f64 = 1.5;
+
+/// What happens to a planet whose position or velocity became NaN or infinite.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+enum NonFinitePolicy {
+    Despawn,
+    /// Keep the planet at its last finite position and stop it
+    Stop,
+}
+
+/// How far from a planet edge (in pixels) a click still selects it
+const SELECT_TOLERANCE_PX: f32 = 5.0;
+/// Fraction of the view height the planet fills after "Zoom to selected"
+const FOCUS_FRACTION: f32 = 0.1;
+/// Drawn size of the anchors, they never collide so it has no physical meaning
+const ANCHOR_RADIUS: f32 = 4.0;
+
+/// Radius of the shepherd moons embedded in the ring scenario
+const SHEPHERD_RADIUS: f32 = 6.0;
+/// Upper bound of the integration steps of the launch preview, wide orbits are cut short
+const MAX_PREVIEW_STEPS: usize = 20000;
+/// Number of points of the launch preview path
+const PREVIEW_POINTS: usize = 200;
+/// Random velocity added to ring particles as a fraction of their orbital speed
+const RING_VELOCITY_DISPERSION: f32 = 0.01;
+/// Radius of the planet the particles of the resonances scenario are in resonance with
+const RESONANT_PLANET_RADIUS: f32 = 15.0;
+/// Period ratios offered by the resonances scenario, (p, q) means p orbits of a particle
+/// per q orbits of the planet: the first ones lie inside the planet orbit, the last ones outside
+const RESONANCES: [(u32, u32); 6] = [(2, 1), (3, 2), (5, 3), (4, 3), (2, 3), (1, 2)];
+/// Number of fragments a tidally disrupted planet breaks into
+const TIDAL_FRAGMENTS: usize = 4;
+/// Only bodies this many times more massive than a planet can tear it apart
+const TIDAL_MASS_RATIO: f32 = 100.0;
+/// Number of the most massive bodies checked for tidal disruption
+const TIDAL_PRIMARIES: usize = 8;
+/// Velocity difference between neighbouring fragments as a fraction of the relative speed
+const TIDAL_VELOCITY_SPREAD: f32 = 0.05;
+/// Seconds the frame rate has to stay below the target before planets are removed
+const AUTO_SCALE_DELAY: f64 = 2.0;
+/// Fraction of the planets removed at once when the frame rate is too low, or added back
+/// when it has room again
+const AUTO_SCALE_FRACTION: f32 = 0.1;
+/// Frames per second above `Settings.auto_scale_fps` before planets are added back
+const AUTO_SCALE_MARGIN: f32 = 10.0;
+
+/// Initial configuration built on `Reset`.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+enum Scenario {
+    /// Planets on random orbits between the minimum and maximum orbit radius
+    ManyOrbits,
+    /// Dense thin ring of small particles with two shepherd moons carving gaps
+    Ring,
+    /// Dense belt of tiny bodies between two radii
+    Belt,
+    /// Real bodies read from a JSON ephemeris
+    Ephemeris,
+    /// Bodies listed in a TOML scene file, passed with `--scene`
+    Scene,
+    /// Rings of particles in mean motion resonances with a massive planet
+    Resonances,
+}
+
+impl std::str::FromStr for Scenario {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "manyorbits" | "many_orbits" => Ok(Scenario::ManyOrbits),
+            "ring" => Ok(Scenario::Ring),
+            "belt" => Ok(Scenario::Belt),
+            "ephemeris" => Ok(Scenario::Ephemeris),
+            "scene" => Ok(Scenario::Scene),
+            "resonances" => Ok(Scenario::Resonances),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Scenario {
+    /// Some scenarios fall apart with merging, they run without collisions whatever
+    /// `Settings.collisions` says.
+    fn allows_collisions(self) -> bool {
+        match self {
+            // ring particles are packed too densely to survive merging
+            Scenario::Ring => false,
+            // the particles share orbits, merging would sweep them up
+            Scenario::Resonances => false,
+            _ => true,
+        }
+    }
+
+    /// Sets the gravity, time step and body counts the scenario looks good with.
+    fn apply_defaults(self, settings: &mut Settings) {
+        match self {
+            Scenario::ManyOrbits => {
+                settings.g = 3.5;
+                settings.time_step = 120.0;
+                settings.n_objects = 500;
+                settings.collisions = true;
+            }
+            Scenario::Ring => {
+                // the tightly packed particles need smaller steps
+                settings.g = 3.5;
+                settings.time_step = 240.0;
+                settings.ring_particles = 800;
+            }
+            Scenario::Belt => {
+                settings.g = 2.0;
+                settings.time_step = 120.0;
+                settings.belt_particles = 1500;
+                settings.collisions = true;
+            }
+            Scenario::Ephemeris => {
+                // the inner planets orbit much faster than the outer ones
+                settings.g = 3.5;
+                settings.time_step = 480.0;
+                settings.collisions = true;
+            }
+            // the scene file brings its own settings
+            Scenario::Scene => {}
+            Scenario::Resonances => {
+                settings.g = 3.5;
+                settings.time_step = 240.0;
+                settings.resonance_particles = 60;
+            }
+        }
+    }
 }
 
-#[derive(Clone)]
+/// Serialized to start `duplicate_simulation` copies with the same settings, the missing
+/// fields take their default value.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
 struct Settings {
+    /// Seed of the random generator used to build the scene and the star-field
+    seed: u64,
+    scenario: Scenario,
+    /// Don't apply the scenario defaults when another scenario is picked
+    keep_settings_on_scenario_change: bool,
     n_objects: usize,
     collisions: bool,
+    merge_policy: MergePolicy,
+    /// Push apart overlapping planets which don't merge, by `overlap_push` of the overlap per step
+    resolve_overlaps: bool,
+    overlap_push: f32,
+    /// Planets merge when their centers are within this fraction of the sum of their radii
+    merge_overlap: f32,
+    /// Look for collisions only between planets in neighboring cells of a grid
+    collision_grid: bool,
+    /// Collisions faster than `debris_speed_threshold` eject `debris_count` fragments
+    impact_debris: bool,
+    debris_speed_threshold: f32,
+    debris_count: usize,
+    merge_dimension: MergeDimension,
+    /// The absorbed planet shrinks and fades into the merged one instead of vanishing,
+    /// the merge itself still happens at once
+    animate_merges: bool,
+    /// Planets within the Roche limit of a much larger body break into fragments
+    enable_tidal_disruption: bool,
+    roche_coefficient: f32,
+    /// Merged planets get the mass weighted average color of their parents
+    blend_colors_on_merge: bool,
+    /// Draw the egui windows into the screenshots
+    screenshot_ui: bool,
     min_planet_size: f32,
     max_planet_size: f32,
     min_planet_density: f32,
     max_planet_density: f32,
     min_planet_orbit_radius: f32,
     max_planet_orbit_radius: f32,
+    max_eccentricity: f32,
+    mass_distribution: MassDistribution,
+    /// Own colors of the generated planets, merges blend them with `blend_colors_on_merge`
+    palette: Palette,
+    /// Exponent of the mass power law p(m) ∝ m^-slope, 2.35 is the Salpeter stellar mass function
+    power_law_slope: f32,
+    /// Fraction of the planets orbiting clockwise, against the rest of the system
+    retrograde_fraction: f32,
+    /// Planets bounce elastically off the walls of a rectangle centered at the origin
+    arena: bool,
+    arena_width: f32,
+    arena_height: f32,
+    /// Relative size difference of the nearest and the middle planets, a cosmetic depth cue
+    depth_scatter: f32,
+    /// Random kick added to the planet velocities, up to this fraction of their orbital speed
+    velocity_jitter: f32,
+    ring_inner_radius: f32,
+    ring_outer_radius: f32,
+    ring_particles: usize,
+    belt_inner_radius: f32,
+    belt_outer_radius: f32,
+    belt_particles: usize,
+    /// Random part of the belt particle velocity as a fraction of the orbital speed
+    belt_dispersion: f32,
+    /// Orbit radius of the planet of the resonances scenario
+    resonant_planet_orbit: f32,
+    /// Particles placed on each of the enabled `RESONANCES`
+    resonance_particles: usize,
+    /// Which of the `RESONANCES` get a ring of particles
+    resonances: [bool; RESONANCES.len()],
+    /// JSON file with the bodies, empty means the bundled solar system
+    ephemeris_path: String,
+    /// TOML file read by `Scenario::Scene`
+    scene_path: String,
+    /// Scene made by `scene::encode`, read by `Scenario::Scene` instead of `scene_path`
+    /// when not empty
+    scene_code: String,
+    /// Simulation units per astronomical unit
+    ephemeris_distance_scale: f32,
+    /// Simulation mass units per solar mass
+    ephemeris_mass_scale: f32,
     sun_size: f32,
     sun_density: f32,
+    cull_escaped: bool,
+    cull_distance: f32,
+    /// Upper bound of the number of planets, the least massive ones are removed above it
+    max_bodies: usize,
+    /// Remove the least massive planets while the frame rate stays below `auto_scale_fps`
+    auto_scale_bodies: bool,
+    auto_scale_fps: f32,
+    /// The automatic removal never goes below this number of planets
+    auto_scale_min_bodies: usize,
+    /// Spawn planets on new orbits again while the frame rate stays above the target
+    auto_scale_respawn: bool,
+    /// The automatic respawn never goes above this number of planets
+    auto_scale_max_bodies: usize,
     g: f32,
+    /// Scale the velocities by sqrt(new g / old g) when `g` changes so orbits keep their shape
+    rescale_on_g_change: bool,
+    /// Coefficient of the relativistic precession term, zero disables it
+    gr_correction: f32,
+    /// Drag coefficient of a gas disk circling the heaviest body, zero disables it
+    gas_drag: f32,
+    softening_mode: SofteningMode,
+    /// Softening length, or factor of the pair size with `SofteningMode::RadiusScaled`
+    softening: f32,
+    /// Planets lighter than `test_particle_mass` only feel the heavier bodies, which makes
+    /// the force computation linear in their number
+    test_particle_mode: bool,
+    test_particle_mass: f32,
     time_step: f32,
+    /// Steps of `1 / (time_step · substeps)` computed per frame, the pace stays the same
+    /// but every frame costs `substeps` force computations
+    substeps: usize,
+    /// Integrate only on every n-th frame with an n times longer step, the pace stays
+    /// the same while the motion gets choppier
+    physics_every_n_frames: usize,
+    /// Rescale the velocities every `energy_correction_interval` steps to keep the total
+    /// energy constant, not physical but it stops the integrator drift in long runs.
+    /// Off while `gas_drag` takes energy out
+    energy_correction: bool,
+    energy_correction_interval: usize,
+    /// Pause right before the first merge after a reset
+    pause_on_collision: bool,
+    /// Move the camera to the collision which paused the simulation
+    center_on_collision: bool,
+    /// Slow the simulation down while two planets are closer than `slow_motion_distance`,
+    /// to `slow_motion_factor` of the normal pace as their centers meet
+    slow_motion: bool,
+    slow_motion_distance: f32,
+    slow_motion_factor: f32,
+    integrator: Integrator,
+    /// Run a tinted copy of the scene next to the main one, advanced with `comparison_integrator`
+    compare_integrators: bool,
+    comparison_integrator: Integrator,
+    /// Frames per second the native build is capped to, 0 means uncapped
+    target_fps: u32,
+    vsync: bool,
+    /// Multisample anti-aliasing samples per pixel, smoother planet edges but slower
+    msaa_samples: u32,
+    render_mode: RenderMode,
+    body_shape: BodyShape,
+    non_finite_policy: NonFinitePolicy,
+    /// Simulated time is shown multiplied by this factor in `sim_time_unit`
+    sim_time_scale: f32,
+    sim_time_unit: String,
+    /// Fake viewing angle in degrees, the orbital plane is squashed vertically by its cosine
+    perspective_tilt: f32,
+    /// Draw lensing-like rings around the most massive body, purely cosmetic
+    lensing: bool,
+    lensing_strength: f32,
+    /// Orbit of the planet placed with the "Launch" button
+    launch_semi_major_axis: f32,
+    launch_eccentricity: f32,
+    /// Direction of the periapsis of the launched orbit in degrees
+    launch_periapsis_angle: f32,
+    /// Draw the predicted path of the planet before launching it
+    launch_preview: bool,
+    /// Number of steps the "Predict" button looks ahead
+    prediction_steps: usize,
+    /// Largest velocity change of the "Stir" button
+    stir_strength: f32,
+    /// Velocity change of the planet under the cursor per arrow key press
+    nudge_speed: f32,
+    /// Fraction of the heat a planet radiates away per simulated time unit
+    cooling_rate: f32,
+    /// Traces of fast planets live longer and those of slow planets shorter, like motion blur
+    speed_trails: bool,
+    /// Trace lifetime in multiples of `TRACE_LIFETIME` per unit of speed with `speed_trails`
+    speed_trail_sensitivity: f32,
+    /// Outline width of the planets follows their acceleration, strong fields stand out
+    acceleration_outlines: bool,
+    /// Outline width in world units per unit of acceleration with `acceleration_outlines`
+    acceleration_outline_scale: f32,
+    /// Length of the momentum arrow in world units per unit of momentum
+    momentum_arrow_scale: f32,
+    /// Mass of the anchors placed by clicking, zero makes them purely visual
+    anchor_mass: f32,
+    /// Bounds of the camera scale in world units per pixel, for zooming in and out
+    min_zoom: f32,
+    max_zoom: f32,
+    /// Number of planets added or removed at once by the +/- buttons
+    planet_count_step: usize,
+    /// Append energy, momentum and barycenter to a CSV file every `log_interval` steps
+    #[cfg(not(target_arch = "wasm32"))]
+    log_diagnostics: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    log_path: String,
+    #[cfg(not(target_arch = "wasm32"))]
+    log_interval: usize,
+    /// Append a CSV line to `merge_log_path` for every merge
+    #[cfg(not(target_arch = "wasm32"))]
+    log_merges: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    merge_log_path: String,
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Self {
+            seed: rand::random(),
+            scenario: Scenario::ManyOrbits,
+            keep_settings_on_scenario_change: false,
             n_objects: 500,
             collisions: true,
+            merge_policy: MergePolicy::default(),
+            resolve_overlaps: false,
+            overlap_push: 0.5,
+            merge_overlap: 1.0,
+            collision_grid: false,
+            impact_debris: false,
+            debris_speed_threshold: 5.0,
+            debris_count: 6,
+            merge_dimension: MergeDimension::Volume,
+            animate_merges: false,
+            enable_tidal_disruption: false,
+            roche_coefficient: 2.44,
+            blend_colors_on_merge: false,
+            screenshot_ui: true,
             min_planet_size: 0.5,
             max_planet_size: 3.5,
             min_planet_density: 0.5,
             max_planet_density: 2.0,
             min_planet_orbit_radius: 100.0,
             max_planet_orbit_radius: 1000.0,
+            max_eccentricity: 0.0,
+            mass_distribution: MassDistribution::Uniform,
+            palette: Palette::White,
+            power_law_slope: 2.35,
+            retrograde_fraction: 0.0,
+            arena: false,
+            arena_width: 2500.0,
+            arena_height: 2500.0,
+            velocity_jitter: 0.0,
+            depth_scatter: 0.0,
+            ring_inner_radius: 300.0,
+            ring_outer_radius: 450.0,
+            ring_particles: 800,
+            belt_inner_radius: 400.0,
+            belt_outer_radius: 600.0,
+            belt_particles: 1000,
+            belt_dispersion: 0.02,
+            resonant_planet_orbit: 500.0,
+            resonance_particles: 60,
+            resonances: [true, true, false, false, false, true],
+            ephemeris_path: String::new(),
+            scene_path: String::new(),
+            scene_code: String::new(),
+            ephemeris_distance_scale: 60.0,
+            ephemeris_mass_scale: 500000.0,
             sun_size: 30.0,
             sun_density: 5.0,
+            cull_escaped: false,
+            cull_distance: 5000.0,
+            max_bodies: 3000,
+            auto_scale_bodies: false,
+            auto_scale_fps: 30.0,
+            auto_scale_min_bodies: 100,
+            auto_scale_respawn: false,
+            auto_scale_max_bodies: 1000,
             g: 3.5,
+            rescale_on_g_change: false,
+            gr_correction: 0.0,
+            gas_drag: 0.0,
+            softening_mode: SofteningMode::Constant,
+            softening: 0.0,
+            test_particle_mode: false,
+            test_particle_mass: 10000.0,
             time_step: 120.0,
+            substeps: 1,
+            physics_every_n_frames: 1,
+            energy_correction: false,
+            energy_correction_interval: 100,
+            pause_on_collision: false,
+            center_on_collision: true,
+            slow_motion: false,
+            slow_motion_distance: 50.0,
+            slow_motion_factor: 0.1,
+            integrator: Integrator::SemiImplicitEuler,
+            compare_integrators: false,
+            comparison_integrator: Integrator::ExplicitEuler,
+            target_fps: 0,
+            vsync: true,
+            msaa_samples: 4,
+            render_mode: RenderMode::Shapes,
+            body_shape: BodyShape::Circle,
+            non_finite_policy: NonFinitePolicy::Despawn,
+            sim_time_scale: 1.0,
+            sim_time_unit: "units".to_string(),
+            perspective_tilt: 0.0,
+            lensing: false,
+            lensing_strength: 0.1,
+            launch_semi_major_axis: 500.0,
+            launch_eccentricity: 0.3,
+            launch_periapsis_angle: 0.0,
+            launch_preview: false,
+            prediction_steps: 2000,
+            stir_strength: 5.0,
+            nudge_speed: 1.0,
+            cooling_rate: 0.5,
+            speed_trails: false,
+            speed_trail_sensitivity: 0.05,
+            acceleration_outlines: false,
+            acceleration_outline_scale: 1.0,
+            momentum_arrow_scale: 0.01,
+            anchor_mass: 100000.0,
+            min_zoom: 0.00001,
+            max_zoom: 50.0,
+            planet_count_step: 50,
+            #[cfg(not(target_arch = "wasm32"))]
+            log_diagnostics: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            log_path: "nbody_diagnostics.csv".to_string(),
+            #[cfg(not(target_arch = "wasm32"))]
+            log_interval: 10,
+            #[cfg(not(target_arch = "wasm32"))]
+            log_merges: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            merge_log_path: "nbody_merges.csv".to_string(),
         }
     }
 }
 
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+enum SimulationLabel {
+    Gravity,
+    ClassifyOrbits,
+}
+
 struct ClearTraces;
 struct Reset;
+struct Launch;
+/// Draws where every planet will be in `Settings.prediction_steps` steps
+struct Predict;
+/// Kicks every planet in a random direction, by at most `Settings.stir_strength`
+struct Stir;
+/// Puts the current planets and settings as a scene code on the clipboard
+struct CopyScene;
+/// Starts the scene code of the settings, in the web build read from the clipboard first
+struct PasteScene;
+/// Adds (positive) or removes (negative) planets without resetting the simulation
+struct ChangePlanetCount(i64);
 
 #[derive(Component, Debug, Clone, Deref)]
 struct Velocity(Vec2);
@@ -76,9 +877,118 @@ struct Planet {
     is_sun: bool,
 }
 
+/// Collision which paused the simulation right before the planets merged.
+#[derive(Clone, Copy)]
+struct PausedCollision {
+    first: Option<BodyId>,
+    second: Option<BodyId>,
+    position: Vec2,
+}
+
+/// Marks the planet picked with a mouse click.
+#[derive(Component)]
+struct Selected;
+
+/// Marks the planet picked with shift+click, its orbit around the selected one is shown.
+#[derive(Component)]
+struct BinaryPartner;
+
+/// Planet placed by the user which attracts the others but never moves or merges
+#[derive(Component)]
+struct Anchor;
+
+/// Identifier which, unlike the entity, survives merges: the merged planet keeps
+/// the id of the heavier parent. The copies of the comparison universe keep the id of
+/// their original.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+struct BodyId(u64);
+
+/// Next free `BodyId`
+#[derive(Default)]
+struct BodyIdCounter(u64);
+
+/// Sort key of the planets in the physics, planets which didn't get their id yet come last
+/// in query order.
+fn body_order(body_ids: &Query<&BodyId>, entity: Entity) -> u64 {
+    body_ids.get(entity).map_or(u64::MAX, |id| id.0)
+}
+
+/// Accretion history of a planet, like `BodyId` it is passed on to the merged planet.
+#[derive(Component, Clone, Copy, Debug)]
+struct Lineage {
+    /// Simulated time at which the oldest ancestor appeared
+    spawn_time: f64,
+    /// Number of merges in the whole family tree, both parents' counts plus one per merge
+    merges: u32,
+}
+
+/// Heat of a planet, raised by the energy lost in collisions and radiated away
+/// at `Settings.cooling_rate`. Planets without it are cold.
+#[derive(Component, Clone, Copy, Debug)]
+struct Temperature(f32);
+
+struct MergeRecord {
+    survivor: BodyId,
+    absorbed: BodyId,
+    sim_time: f64,
+    survivor_mass: f32,
+    absorbed_mass: f32,
+    /// Less than the sum of the parents when the impact ejected debris
+    merged_mass: f32,
+    impact_speed: f32,
+}
+
+/// Every merge since the last `Reset`, oldest first
+#[derive(Default)]
+struct MergeLog(Vec<MergeRecord>);
+
+/// Identity passed on in a merge. A planet merged during a frame only gets its components
+/// once the commands are applied, a later substep of the same frame reads them from here.
+#[derive(Clone, Default)]
+struct Carried {
+    id: Option<BodyId>,
+    lineage: Option<Lineage>,
+    name: Option<Name>,
+    temperature: f32,
+    selected: bool,
+    partner: bool,
+}
+
+/// Copy of the scene advanced with `Settings.comparison_integrator`, it doesn't interact
+/// with the planets without this marker
+#[derive(Component)]
+struct Comparison;
+
+/// Acceleration of the planet in the last physics step, kept for `outline_acceleration`.
+#[derive(Component, Clone, Copy, Default)]
+struct Acceleration(Vec2);
+
+/// Where the planet dropped its last trace point, used with `Stats.trace_spacing`.
+#[derive(Component)]
+struct LastTrace(Vec2);
+
+/// Expanding ring shown where two planets merged.
+#[derive(Component)]
+struct Flash {
+    spawned_at: f64,
+    max_radius: f32,
+}
+
+/// Absorbed planet which is no longer simulated, only drawn moving into the merged one.
+#[derive(Component)]
+struct Absorbing {
+    into: Entity,
+    from: Vec2,
+    spawned_at: f64,
+}
+
 #[derive(Component)]
 struct Trace {
     live_until: f64,
+    /// Seconds between the spawn and `live_until`
+    lifetime: f64,
+    /// Alpha of the trace before it starts fading
+    opacity: f32,
 }
 
 impl Planet {
@@ -87,151 +997,1924 @@ impl Planet {
     }
 }
 
-fn move_camera(mut camera: Query<&mut Transform, With<Camera>>, stats: Res<Stats>) {
+fn move_camera(
+    mut camera: Query<&mut Transform, With<Camera>>,
+    selected: Query<&Transform, (With<Selected>, Without<Camera>)>,
+    stats: Res<Stats>,
+) {
     for mut transform in camera.iter_mut() {
-        if stats.center_on_largest {
+        if let (true, Some(selected)) = (stats.follow_selected, selected.iter().next()) {
+            transform.translation.x = selected.translation.x;
+            transform.translation.y = selected.translation.y;
+        } else if stats.center_on_largest {
             transform.translation.x = stats.largest_position.x;
             transform.translation.y = stats.largest_position.y;
         }
     }
 }
 
+/// Gives the 2D simulation a faux 3D look by stretching the camera vertically,
+/// which draws the planets, orbits and traces squashed as if seen at an angle.
+fn apply_perspective_tilt(
+    settings: Res<Settings>,
+    mut camera: Query<&mut Transform, With<PanCam>>,
+) {
+    let stretch = 1.0 / settings.perspective_tilt.to_radians().cos();
+    for mut transform in camera.iter_mut() {
+        if transform.scale.y != stretch {
+            transform.scale.y = stretch;
+        }
+    }
+}
+
 fn gravity(
     mut commands: Commands,
     settings: Res<Settings>,
-    mut planet_query: Query<(Entity, &mut Planet, &mut Velocity, &mut Transform)>,
+    mut planet_query: Query<(
+        Entity,
+        &mut Planet,
+        &mut Velocity,
+        &mut Transform,
+        Option<&mut Acceleration>,
+    )>,
+    selected: Query<Entity, With<Selected>>,
+    partners: Query<(), With<BinaryPartner>>,
+    anchors: Query<(), With<Anchor>>,
+    comparison: Query<(), With<Comparison>>,
+    (body_ids, names): (Query<&BodyId>, Query<&Name>),
+    lineages: Query<&Lineage>,
+    temperatures: Query<&Temperature>,
+    last_traces: Query<&LastTrace>,
+    mut merge_log: ResMut<MergeLog>,
+    replay: Res<Replay>,
     mut stats: ResMut<Stats>,
     time: Res<Time>,
+    mut skipped_frames: Local<usize>,
 ) {
-    let mut accel_map: HashMap<u32, Vec2> = HashMap::new();
-    let mut despawned = HashSet::new();
+    // the replay moves the planets instead
+    if replay.is_playing() {
+        return;
+    }
+    if stats.paused && !std::mem::take(&mut stats.step_pending) {
+        return;
+    }
+    // on the skipped frames the planets stand still, the next step covers them as well
+    let frames_per_step = settings.physics_every_n_frames.max(1);
+    if *skipped_frames + 1 < frames_per_step {
+        *skipped_frames += 1;
+        return;
+    }
+    *skipped_frames = 0;
+    let mut step = frames_per_step as f32 / settings.time_step;
+    // the closest pair comes from the previous step, the pace comes back as the planets part
+    if settings.slow_motion {
+        if let Some((_, _, distance)) = stats.closest_pair {
+            let t = (distance / settings.slow_motion_distance).clamp(0.0, 1.0);
+            step *= settings.slow_motion_factor + (1.0 - settings.slow_motion_factor) * t;
+        }
+    }
+    let mut entities = Vec::new();
+    let mut bodies = Vec::new();
+    let previous_count = stats.n_objects;
     stats.n_objects = 0;
-    let mut largest = 0.0;
     stats.frame_number += 1;
 
-    for (entity_1, planet_1, velocity_1, transform_1) in planet_query.iter() {
-        if stats.frame_number % 5 == 0 && stats.draw_traces {
-            let mut transform: Transform = *transform_1;
-            transform.translation.z = 1.0;
-            spawn_trace(
+    let mut planets: Vec<_> = planet_query.iter().collect();
+    // the query order depends on the archetype storage, sorting by id makes the order of
+    // the collisions and so the whole run reproducible for a seed. Entities are reused after
+    // despawning, unlike the body ids which are handed out in the order the planets appear.
+    planets.sort_by_key(|(entity, _, _, _, _)| body_order(&body_ids, *entity));
+    for (entity, planet, velocity, transform, _) in planets {
+        if comparison.get(entity).is_err() {
+            stats.n_objects += 1;
+        }
+        entities.push((entity, planet.clone(), *transform));
+        bodies.push(Body {
+            position: transform.translation.truncate(),
+            velocity: velocity.0,
+            mass: planet.mass(),
+            radius: planet.radius,
+            fixed: anchors.get(entity).is_ok(),
+            is_sun: planet.is_sun,
+        });
+    }
+    stats.largest_position = largest_planet_position(&bodies);
+
+    let sample_traces =
+        stats.trace_spacing > 0.0 || stats.frame_number % TRACE_INTERVAL_FRAMES == 0;
+    if sample_traces && stats.trace_mode != TraceMode::None {
+        let min_traced_mass = match stats.trace_mode {
+            TraceMode::LargestN => nth_largest_mass(&bodies, stats.trace_largest_n),
+            _ => 0.0,
+        };
+        for (i, (entity, planet, transform)) in entities.iter().enumerate() {
+            let traced = match stats.trace_mode {
+                TraceMode::None => false,
+                TraceMode::All => true,
+                TraceMode::Selected => selected.get(*entity).is_ok(),
+                TraceMode::LargestN => bodies[i].mass >= min_traced_mass,
+            };
+            if traced {
+                if stats.trace_spacing > 0.0 {
+                    // evenly spaced points whatever the speed and the frame rate
+                    let position = transform.translation.truncate();
+                    if let Ok(last) = last_traces.get(*entity) {
+                        if last.0.distance(position) < stats.trace_spacing {
+                            continue;
+                        }
+                    }
+                    commands.entity(*entity).insert(LastTrace(position));
+                }
+                let lifetime = if settings.speed_trails {
+                    let speed = bodies[i].velocity.length();
+                    TRACE_LIFETIME
+                        * (speed * settings.speed_trail_sensitivity).min(MAX_SPEED_TRAIL) as f64
+                } else {
+                    TRACE_LIFETIME
+                };
+                // nearly resting planets leave no trace at all
+                if lifetime < TRACE_LIFETIME * 0.01 {
+                    continue;
+                }
+                let mut transform: Transform = *transform;
+                transform.translation.z = trace_layer(&stats);
+                let color = if stats.gray_traces {
+                    Color::GRAY
+                } else {
+                    planet.color
+                };
+                spawn_trace(
+                    &mut commands,
+                    transform,
+                    (time.seconds_since_startup(), lifetime),
+                    color,
+                    stats.glow_traces,
+                );
+            }
+        }
+    }
+
+    if stats.comparison_pending {
+        stats.comparison_pending = false;
+        for (i, (entity, planet, transform)) in entities.iter().enumerate() {
+            let copy = Planet {
+                color: comparison_tint(planet.color),
+                ..planet.clone()
+            };
+            let copy = spawn_planet(
                 &mut commands,
-                transform,
-                time.seconds_since_startup() + 10.0,
+                copy,
+                Velocity(bodies[i].velocity),
+                *transform,
             );
+            commands.entity(copy).insert(Comparison);
+            if anchors.get(*entity).is_ok() {
+                commands.entity(copy).insert(Anchor);
+            }
+            if let Ok(id) = body_ids.get(*entity) {
+                commands.entity(copy).insert(*id);
+            }
         }
-        if planet_1.radius > largest && !planet_1.is_sun {
-            largest = planet_1.radius;
-            stats.largest_position = transform_1.translation.truncate();
+        // the copies only exist from the next frame on, wait for them so that
+        // both integrators start from the same state
+        return;
+    }
+
+    // the comparison planets form a separate system which doesn't interact with the main one
+    let (main, copies): (Vec<usize>, Vec<usize>) =
+        (0..entities.len()).partition(|&i| comparison.get(entities[i].0).is_err());
+    let start_integrator = std::mem::take(&mut stats.integrator_start_pending);
+    let mut merges = 0;
+    // smaller steps are more accurate during close encounters, the pace stays the same
+    let substeps = settings.substeps.max(1);
+    let dt = step / substeps as f32;
+    // substeps taken before a collision paused the main planets, the comparison copies
+    // stop after as many so both stay at the same simulated time
+    let mut paused_after = None;
+    let carried_by = |merged: &HashMap<Entity, Carried>, entity: Entity| {
+        merged.get(&entity).cloned().unwrap_or_else(|| Carried {
+            id: body_ids.get(entity).ok().copied(),
+            lineage: lineages.get(entity).ok().copied(),
+            name: names.get(entity).ok().cloned(),
+            temperature: temperatures.get(entity).map_or(0.0, |t| t.0),
+            selected: selected.get(entity).is_ok(),
+            partner: partners.get(entity).is_ok(),
+        })
+    };
+    let universes = [
+        (main, settings.integrator, false),
+        (copies, settings.comparison_integrator, true),
+    ];
+    for (indices, integrator, is_comparison) in universes {
+        let substeps = if is_comparison {
+            paused_after.unwrap_or(substeps)
+        } else {
+            substeps
+        };
+        if indices.is_empty() || substeps == 0 {
+            continue;
         }
-        stats.n_objects += 1;
-        let mut accel_cum = Vec2::new(0.0, 0.0);
-        for (entity_2, planet_2, velocity_2, transform_2) in planet_query.iter() {
-            if entity_1.id() != entity_2.id()
-                && !despawned.contains(&entity_1.id())
-                && !despawned.contains(&entity_2.id())
-            {
-                let r_vector =
-                    transform_1.translation.truncate() - transform_2.translation.truncate();
-                if r_vector.length() < planet_1.radius + planet_2.radius && settings.collisions {
-                    let sum_mass = planet_1.mass() + planet_2.mass();
-                    let final_velocity = Velocity(
-                        velocity_1.0 * planet_1.mass() / sum_mass
-                            + velocity_2.0 * planet_2.mass() / sum_mass,
-                    );
-                    commands.entity(entity_2).despawn();
-                    despawned.insert(entity_2.id());
-                    commands.entity(entity_1).despawn();
-                    despawned.insert(entity_1.id());
-                    if planet_1.mass() > planet_2.mass() {
-                        spawn_planet(
-                            &mut commands,
-                            merge_planets(planet_1, &planet_2),
-                            final_velocity,
-                            *transform_1,
-                        );
-                    } else {
-                        spawn_planet(
-                            &mut commands,
-                            merge_planets(planet_2, &planet_1),
-                            final_velocity,
-                            *transform_2,
-                        );
+        let mut entities: Vec<_> = indices.iter().map(|&i| entities[i].clone()).collect();
+        let mut bodies: Vec<_> = indices.iter().map(|&i| bodies[i]).collect();
+
+        let totals = physics::system_totals(&bodies);
+        if !is_comparison {
+            stats.barycenter = totals.barycenter;
+            stats.momentum = totals.momentum;
+            stats.total_mass = totals.mass;
+            stats.angular_momentum = totals.angular_momentum;
+            if stats.initial_angular_momentum.is_none() {
+                stats.initial_angular_momentum = Some(totals.angular_momentum);
+            }
+        }
+        if settings.cull_escaped {
+            for i in (0..bodies.len()).rev() {
+                if bodies[i].position.distance(totals.barycenter) > settings.cull_distance {
+                    commands.entity(entities[i].0).despawn();
+                    entities.remove(i);
+                    bodies.remove(i);
+                    if !is_comparison {
+                        stats.n_objects -= 1;
+                        stats.ejected_count += 1;
                     }
+                }
+            }
+        }
+
+        let mut accelerations = Vec::new();
+        // identity of the planets merged in the earlier substeps
+        let mut merged_this_frame = HashMap::new();
+        for substep in 0..substeps {
+            let mut forces =
+                physics::compute_forces(&bodies, &force_params(&settings, stats.scenario));
+            if settings.gas_drag > 0.0 {
+                physics::apply_gas_drag(&bodies, &mut forces, settings.g, settings.gas_drag);
+            }
+            if !is_comparison {
+                stats.closest_pair = forces
+                    .closest
+                    .map(|(i, j, distance)| (entities[i].0, entities[j].0, distance));
+            }
+
+            // stop before the merge so the touching planets can be inspected, the next
+            // step merges them
+            let pause =
+                settings.pause_on_collision && !is_comparison && stats.paused_collision.is_none();
+            if let (true, Some(collision)) = (pause, forces.collisions.first()) {
+                let (entity_1, entity_2) =
+                    (entities[collision.first].0, entities[collision.second].0);
+                stats.paused_collision = Some(PausedCollision {
+                    first: carried_by(&merged_this_frame, entity_1).id,
+                    second: carried_by(&merged_this_frame, entity_2).id,
+                    position: (bodies[collision.first].position
+                        + bodies[collision.second].position)
+                        / 2.0,
+                });
+                stats.paused = true;
+                stats.focus_collision_pending = settings.center_on_collision;
+                accelerations = std::mem::take(&mut forces.accelerations);
+                paused_after = Some(substep);
+                // the integrator starts with the step taken after the pause
+                if substep == 0 {
+                    stats.integrator_start_pending = start_integrator;
+                }
+                break;
+            }
+
+            merges += forces.collisions.len();
+            if !is_comparison {
+                stats.collision_count += forces.collisions.len();
+            }
+            let mut spawned = Vec::new();
+            for collision in forces.collisions.iter() {
+                let (entity_1, planet_1, transform_1) = &entities[collision.first];
+                let (entity_2, planet_2, transform_2) = &entities[collision.second];
+                let carried_1 = carried_by(&merged_this_frame, *entity_1);
+                let carried_2 = carried_by(&merged_this_frame, *entity_2);
+                // the transforms are those of the frame start, earlier substeps moved the bodies
+                let at_impact = |transform: &Transform, body: &Body| Transform {
+                    translation: body.position.extend(transform.translation.z),
+                    ..*transform
+                };
+                let transform_1 = at_impact(transform_1, &bodies[collision.first]);
+                let transform_2 = at_impact(transform_2, &bodies[collision.second]);
+                let final_velocity = Velocity(physics::merge_velocity(
+                    &bodies[collision.first],
+                    &bodies[collision.second],
+                ));
+                let (
+                    mut merged_planet,
+                    mut transform,
+                    (survivor, survivor_carried),
+                    (absorbed, absorbed_carried),
+                ) = if planet_1.mass() > planet_2.mass() {
+                    (
+                        merge_planets(planet_1, planet_2, &settings),
+                        transform_1,
+                        (entity_1, &carried_1),
+                        (entity_2, &carried_2),
+                    )
+                } else {
+                    (
+                        merge_planets(planet_2, planet_1, &settings),
+                        transform_2,
+                        (entity_2, &carried_2),
+                        (entity_1, &carried_1),
+                    )
+                };
+                let merged_position =
+                    physics::merge_position(&bodies[collision.first], &bodies[collision.second]);
+                transform.translation = merged_position.extend(transform.translation.z);
+                // the collision energy is shared by the whole merged mass
+                let (mass_1, mass_2) =
+                    (bodies[collision.first].mass, bodies[collision.second].mass);
+                let merged_temperature = (carried_1.temperature * mass_1
+                    + carried_2.temperature * mass_2
+                    + physics::collision_heat(&bodies[collision.first], &bodies[collision.second])
+                        / HEAT_CAPACITY)
+                    / (mass_1 + mass_2);
+                let impact_speed =
+                    (bodies[collision.first].velocity - bodies[collision.second].velocity).length();
+                if settings.impact_debris && impact_speed > settings.debris_speed_threshold {
+                    let lighter = if planet_1.mass() > planet_2.mass() {
+                        planet_2
+                    } else {
+                        planet_1
+                    };
+                    let debris_mass = DEBRIS_MASS_FRACTION * lighter.mass();
+                    // the debris mass is taken from the merged planet, which shrinks and keeps
+                    // its density
+                    let remaining = 1.0 - debris_mass / merged_planet.mass();
+                    merged_planet.radius = physics::volume_to_radius(
+                        physics::radius_to_volume(merged_planet.radius) * remaining,
+                    );
+                    let impact = Impact {
+                        transform,
+                        velocity: final_velocity.0,
+                        speed: impact_speed,
+                        radius: merged_planet.radius,
+                    };
+                    spawn_debris(
+                        &mut commands,
+                        &settings,
+                        lighter,
+                        debris_mass,
+                        &impact,
+                        is_comparison,
+                    );
+                }
+                if stats.merge_flash {
+                    spawn_flash(
+                        &mut commands,
+                        transform.translation.truncate(),
+                        merged_planet.radius * FLASH_SIZE,
+                        time.seconds_since_startup(),
+                    );
+                }
+                let (survivor_mass, absorbed_mass) = if planet_1.mass() > planet_2.mass() {
+                    (mass_1, mass_2)
                 } else {
-                    let r_mag = r_vector.length();
-                    let r_mag = if !settings.collisions && r_mag < planet_1.radius + planet_2.radius
-                    {
-                        planet_1.radius + planet_2.radius
+                    (mass_2, mass_1)
+                };
+                let merged_mass = merged_planet.mass();
+                let merged_body = Body {
+                    position: transform.translation.truncate(),
+                    velocity: final_velocity.0,
+                    mass: merged_mass,
+                    radius: merged_planet.radius,
+                    fixed: false,
+                    is_sun: merged_planet.is_sun,
+                };
+                let merged = spawn_planet(
+                    &mut commands,
+                    merged_planet.clone(),
+                    final_velocity,
+                    transform,
+                );
+                spawned.push(((merged, merged_planet, transform), merged_body));
+                commands.entity(*survivor).despawn();
+                if settings.animate_merges {
+                    let absorbed_transform = if absorbed == entity_1 {
+                        transform_1
                     } else {
-                        r_mag
+                        transform_2
                     };
-                    let accel: f32 = -1.0 * settings.g * planet_2.mass() / r_mag.powf(2.0);
-                    let r_vector_unit = r_vector / r_mag;
-                    accel_cum += accel * r_vector_unit;
+                    commands
+                        .entity(*absorbed)
+                        .remove::<Planet>()
+                        .remove::<Velocity>()
+                        .remove::<Selected>()
+                        .remove::<BinaryPartner>()
+                        // the merged planet carries the identity on
+                        .remove::<BodyId>()
+                        .remove::<Lineage>()
+                        .remove::<Name>()
+                        .remove::<Acceleration>()
+                        .insert(Absorbing {
+                            into: merged,
+                            from: absorbed_transform.translation.truncate(),
+                            spawned_at: time.seconds_since_startup(),
+                        });
+                } else {
+                    commands.entity(*absorbed).despawn();
+                }
+                let carried = Carried {
+                    // the merged planet continues the lineage of the heavier one
+                    id: survivor_carried.id,
+                    lineage: match (survivor_carried.lineage, absorbed_carried.lineage) {
+                        (Some(survivor), Some(absorbed)) => Some(Lineage {
+                            spawn_time: survivor.spawn_time.min(absorbed.spawn_time),
+                            merges: survivor.merges + absorbed.merges + 1,
+                        }),
+                        _ => None,
+                    },
+                    name: survivor_carried
+                        .name
+                        .clone()
+                        .or_else(|| absorbed_carried.name.clone()),
+                    temperature: merged_temperature,
+                    selected: carried_1.selected || carried_2.selected,
+                    partner: carried_1.partner || carried_2.partner,
+                };
+                if carried.selected {
+                    commands.entity(merged).insert(Selected);
+                }
+                if carried.partner {
+                    commands.entity(merged).insert(BinaryPartner);
+                }
+                if is_comparison {
+                    commands.entity(merged).insert(Comparison);
                 }
+                commands
+                    .entity(merged)
+                    .insert(Temperature(carried.temperature));
+                if let Some(lineage) = carried.lineage {
+                    commands.entity(merged).insert(lineage);
+                }
+                if let Some(name) = &carried.name {
+                    commands.entity(merged).insert(name.clone());
+                }
+                if let Some(survivor) = survivor_carried.id {
+                    commands.entity(merged).insert(survivor);
+                }
+                // the copies share the ids of the main planets, only the main merges are logged
+                if let (false, Some(survivor), Some(absorbed)) =
+                    (is_comparison, survivor_carried.id, absorbed_carried.id)
+                {
+                    merge_log.0.push(MergeRecord {
+                        survivor,
+                        absorbed,
+                        sim_time: stats.sim_time,
+                        survivor_mass,
+                        absorbed_mass,
+                        merged_mass,
+                        impact_speed,
+                    });
+                }
+                merged_this_frame.insert(merged, carried);
             }
-        }
-        accel_map.insert(entity_1.id(), accel_cum);
-    }
 
-    for (entity_1, _, mut velocity_1, mut transform_1) in planet_query.iter_mut() {
-        if !despawned.contains(&entity_1.id()) {
-            velocity_1.0 += *accel_map.get(&entity_1.id()).unwrap() * (1.0 / settings.time_step);
-            transform_1.translation.x += velocity_1.x * (1.0 / settings.time_step);
-            transform_1.translation.y += velocity_1.y * (1.0 / settings.time_step);
-        }
-    }
-}
+            if settings.enable_tidal_disruption {
+                disrupt_planets(
+                    &mut commands,
+                    &settings,
+                    &entities,
+                    &bodies,
+                    &mut forces,
+                    is_comparison,
+                );
+            }
 
-fn radius_to_area(r: f32) -> f32 {
-    PI * r.powf(2.0)
-}
+            if start_integrator && substep == 0 && integrator == Integrator::Leapfrog {
+                physics::leapfrog_start(&mut bodies, &forces, dt);
+            }
+            physics::advance(&mut bodies, &forces, dt, integrator);
+            if settings.resolve_overlaps {
+                physics::resolve_overlaps(
+                    &mut bodies,
+                    &forces,
+                    &force_params(&settings, stats.scenario),
+                    settings.overlap_push,
+                );
+            }
+            if settings.arena {
+                let half_size = Vec2::new(settings.arena_width, settings.arena_height) / 2.0;
+                for (i, body) in bodies.iter_mut().enumerate() {
+                    if !forces.merged[i] && !body.fixed {
+                        physics::bounce_off_walls(body, half_size);
+                    }
+                }
+            }
 
-fn radius_to_volume(r: f32) -> f32 {
-    4.0 / 3.0 * PI * r.powf(3.0)
-}
+            // merged and torn apart planets leave the integration and the planets merged from
+            // them join it, the debris and the fragments only from the next frame
+            accelerations = std::mem::take(&mut forces.accelerations);
+            let mut kept = forces.merged.iter().map(|merged| !merged);
+            entities.retain(|_| kept.next().unwrap());
+            let mut kept = forces.merged.iter().map(|merged| !merged);
+            bodies.retain(|_| kept.next().unwrap());
+            let mut kept = forces.merged.iter().map(|merged| !merged);
+            accelerations.retain(|_| kept.next().unwrap());
+            for (entity, body) in spawned {
+                entities.push(entity);
+                bodies.push(body);
+                accelerations.push(Vec2::ZERO);
+            }
+        }
 
-fn area_to_radius(a: f32) -> f32 {
-    (a / PI).sqrt()
-}
+        // the gas drag takes energy out on purpose, the correction would put it back
+        let correct_energy = settings.energy_correction && settings.gas_drag <= 0.0;
+        if correct_energy && !is_comparison {
+            match stats.reference_energy {
+                Some((energy, count, g)) if count == bodies.len() && g == settings.g => {
+                    stats.steps_since_correction += 1;
+                    if stats.steps_since_correction >= settings.energy_correction_interval.max(1) {
+                        stats.steps_since_correction = 0;
+                        physics::rescale_energy(&mut bodies, settings.g, energy);
+                    }
+                }
+                _ => {
+                    let energy = physics::total_energy(&bodies, settings.g);
+                    stats.reference_energy = Some((energy, bodies.len(), settings.g));
+                    stats.steps_since_correction = 0;
+                }
+            }
+        } else if !is_comparison {
+            // switching the correction on keeps the energy of that moment
+            stats.reference_energy = None;
+        }
 
-fn volume_to_radius(v: f32) -> f32 {
-    ((3.0 * v) / (4.0 * PI)).powf(1.0 / 3.0)
-}
+        for (i, (entity, _, spawn_transform)) in entities.iter().enumerate() {
+            if let Ok((_, _, mut velocity, mut transform, acceleration)) =
+                planet_query.get_mut(*entity)
+            {
+                if !bodies[i].position.is_finite() || !bodies[i].velocity.is_finite() {
+                    stats.nan_events += 1;
+                    warn!(
+                        "Planet {} reached a non-finite state {:?}",
+                        entity.id(),
+                        bodies[i]
+                    );
+                    match settings.non_finite_policy {
+                        NonFinitePolicy::Despawn => commands.entity(*entity).despawn(),
+                        // keep the last finite position
+                        NonFinitePolicy::Stop => velocity.0 = Vec2::ZERO,
+                    }
+                    continue;
+                }
+                velocity.0 = bodies[i].velocity;
+                transform.translation.x = bodies[i].position.x;
+                transform.translation.y = bodies[i].position.y;
+                match acceleration {
+                    Some(mut acceleration) => acceleration.0 = accelerations[i],
+                    None => {
+                        commands
+                            .entity(*entity)
+                            .insert(Acceleration(accelerations[i]));
+                    }
+                }
+            } else {
+                // merged during this frame, it only exists once the commands are applied
+                let mut transform = *spawn_transform;
+                transform.translation.x = bodies[i].position.x;
+                transform.translation.y = bodies[i].position.y;
+                commands
+                    .entity(*entity)
+                    .insert(Velocity(bodies[i].velocity))
+                    .insert(transform)
+                    .insert(Acceleration(accelerations[i]));
+            }
+        }
+    }
+    // the step stopped at the collision, the planets moved by the substeps before it
+    let simulated = match paused_after {
+        Some(substep) => substep as f32 * dt,
+        None => step,
+    };
+    stats.sim_time += simulated as f64;
 
-fn merge_planets(planet_1: &Planet, planet_2: &Planet) -> Planet {
-    let volume_1 = radius_to_volume(planet_1.radius);
-    let volume_2 = radius_to_volume(planet_2.radius);
-    let volume_sum = volume_1 + volume_2;
-    let new_radius = volume_to_radius(volume_sum);
-    Planet {
-        radius: new_radius,
-        density: planet_1.density * (volume_1 / volume_sum)
-            + planet_2.density * (volume_2 / volume_sum),
-        color: planet_1.color,
-        is_sun: planet_1.is_sun || planet_2.is_sun,
+    // a pause before the first substep simulated nothing, there is no rate to measure
+    if simulated <= 0.0 {
+        return;
+    }
+    let decline = previous_count.saturating_sub(stats.n_objects) as f32;
+    stats.accretion_rate += (merges as f32 / simulated - stats.accretion_rate) * RATE_SMOOTHING;
+    stats.body_decline_rate += (decline / simulated - stats.body_decline_rate) * RATE_SMOOTHING;
+    let accretion_rate = stats.accretion_rate;
+    stats.accretion_history.push_back(accretion_rate);
+    if stats.accretion_history.len() > ACCRETION_HISTORY {
+        stats.accretion_history.pop_front();
     }
 }
 
-fn despawn_traces(
-    mut ev_clear_trace: EventReader<ClearTraces>,
-    mut commands: Commands,
-    traces: Query<(Entity, &Trace)>,
-    time: Res<Time>,
+/// Tears planets passing within the Roche limit of a much more massive body into
+/// fragments spread along their path. Fragments smaller than the minimum planet size
+/// are not created, which stops the debris from breaking up forever.
+fn disrupt_planets(
+    commands: &mut Commands,
+    settings: &Settings,
+    entities: &[(Entity, Planet, Transform)],
+    bodies: &[Body],
+    forces: &mut physics::Forces,
+    is_comparison: bool,
 ) {
-    let mut manual_clear = false;
-    for _ in ev_clear_trace.iter() {
-        manual_clear = true;
-    }
-    for (entity, trace) in traces.iter() {
-        if trace.live_until < time.seconds_since_startup() || manual_clear {
-            commands.entity(entity).despawn();
+    let mut primaries: Vec<usize> = (0..bodies.len()).filter(|&i| !forces.merged[i]).collect();
+    primaries.sort_by(|&a, &b| bodies[b].mass.total_cmp(&bodies[a].mass));
+    primaries.truncate(TIDAL_PRIMARIES);
+
+    for (i, (entity, planet, transform)) in entities.iter().enumerate() {
+        let body = &bodies[i];
+        let fragment_radius = physics::volume_to_radius(
+            physics::radius_to_volume(body.radius) / TIDAL_FRAGMENTS as f32,
+        );
+        if forces.merged[i]
+            || body.fixed
+            || planet.is_sun
+            || fragment_radius < settings.min_planet_size
+        {
+            continue;
+        }
+        let primary = primaries.iter().map(|&j| &bodies[j]).find(|primary| {
+            primary.mass > body.mass * TIDAL_MASS_RATIO
+                && body.position.distance(primary.position)
+                    < physics::roche_limit(primary, body, settings.roche_coefficient)
+        });
+        let primary = match primary {
+            Some(primary) => primary,
+            None => continue,
+        };
+
+        // the planet is gone, keep it out of the integration like a merged one
+        forces.merged[i] = true;
+        commands.entity(*entity).despawn();
+        let relative_velocity = body.velocity - primary.velocity;
+        let mut along = relative_velocity.normalize_or_zero();
+        if along == Vec2::ZERO {
+            along = (body.position - primary.position)
+                .perp()
+                .normalize_or_zero();
+        }
+        // fragments are placed symmetrically so the momentum is preserved
+        for k in 0..TIDAL_FRAGMENTS {
+            let offset = k as f32 - (TIDAL_FRAGMENTS - 1) as f32 / 2.0;
+            let position = body.position + along * offset * fragment_radius * 2.5;
+            let velocity =
+                body.velocity + along * offset * TIDAL_VELOCITY_SPREAD * relative_velocity.length();
+            let mut transform = *transform;
+            transform.translation.x = position.x;
+            transform.translation.y = position.y;
+            let fragment = Planet {
+                radius: fragment_radius,
+                ..planet.clone()
+            };
+            let fragment = spawn_planet(commands, fragment, Velocity(velocity), transform);
+            if is_comparison {
+                commands.entity(fragment).insert(Comparison);
+            }
         }
     }
 }
 
-fn setup(mut commands: Commands, mut ev_reset: EventWriter<Reset>) {
-    commands
+/// Classifies the planets as bound to or escaping from the largest sun by the sign
+/// of their specific orbital energy.
+fn classify_orbits(
+    settings: Res<Settings>,
+    mut stats: ResMut<Stats>,
+    mut planets: Query<(
+        Entity,
+        &Planet,
+        &Transform,
+        &Velocity,
+        Option<&Lineage>,
+        Option<&Temperature>,
+        &mut DrawMode,
+    )>,
+) {
+    stats.bound_count = 0;
+    stats.unbound_count = 0;
+    let sun = largest_sun(
+        planets
+            .iter()
+            .map(|(entity, planet, transform, velocity, _, _, _)| {
+                (
+                    planet,
+                    (entity, transform.translation.truncate(), velocity.0),
+                )
+            }),
+    )
+    .map(|(sun, state)| (sun.mass(), state));
+    let (sun_mass, (sun_entity, sun_position, sun_velocity)) = match sun {
+        Some(sun) => sun,
+        None => return,
+    };
+
+    let mu = settings.g * sun_mass;
+    for (entity, planet, transform, velocity, lineage, temperature, mut draw_mode) in
+        planets.iter_mut()
+    {
+        if entity == sun_entity {
+            continue;
+        }
+        let energy = physics::specific_orbital_energy(
+            mu,
+            transform.translation.truncate() - sun_position,
+            velocity.0 - sun_velocity,
+        );
+        let bound = energy < 0.0;
+        if bound {
+            stats.bound_count += 1;
+        } else {
+            stats.unbound_count += 1;
+        }
+
+        let color = match (stats.color_mode, lineage, temperature) {
+            _ if !bound && stats.tint_unbound => Color::RED,
+            (ColorMode::MergeCount, Some(lineage), _) => merge_count_color(lineage.merges),
+            (ColorMode::Temperature, _, temperature) => {
+                temperature_color(temperature.map_or(0.0, |t| t.0))
+            }
+            _ => planet.color,
+        };
+        let current = match *draw_mode {
+            DrawMode::Outlined { ref fill_mode, .. } => fill_mode.color,
+            _ => continue,
+        };
+        // only touch the draw mode when needed, changing it rebuilds the mesh
+        if current != color {
+            *draw_mode = DrawMode::Outlined {
+                fill_mode: FillMode::color(color),
+                outline_mode: StrokeMode::new(color, 0.0),
+            };
+        }
+    }
+}
+
+/// Draws a white outline as wide as the acceleration of the planet with
+/// `Settings.acceleration_outlines`. Runs after `classify_orbits`, which resets
+/// the outline whenever it changes the color.
+fn outline_acceleration(
+    settings: Res<Settings>,
+    mut planets: Query<(&Planet, &Acceleration, &mut DrawMode)>,
+) {
+    for (planet, acceleration, mut draw_mode) in planets.iter_mut() {
+        let (fill_color, outline_color, outline_width) = match *draw_mode {
+            DrawMode::Outlined {
+                ref fill_mode,
+                ref outline_mode,
+            } => (
+                fill_mode.color,
+                outline_mode.color,
+                outline_mode.options.line_width,
+            ),
+            _ => continue,
+        };
+        let (color, width) = if settings.acceleration_outlines {
+            // never wider than the planet itself
+            let width = acceleration.0.length() * settings.acceleration_outline_scale;
+            (Color::WHITE, width.min(planet.radius))
+        } else {
+            (fill_color, 0.0)
+        };
+        if outline_color != color || outline_width != width {
+            *draw_mode = DrawMode::Outlined {
+                fill_mode: FillMode::color(fill_color),
+                outline_mode: StrokeMode::new(color, width),
+            };
+        }
+    }
+}
+
+/// Gives new planets an id and a fresh `Lineage`. Runs before the update stage so the planets
+/// spawned in the previous frame have them when `gravity` sees them.
+fn assign_body_ids(
+    mut commands: Commands,
+    mut counter: ResMut<BodyIdCounter>,
+    stats: Res<Stats>,
+    planets: Query<Entity, (With<Planet>, Without<BodyId>)>,
+    newborns: Query<Entity, (With<Planet>, Without<Lineage>)>,
+) {
+    for entity in planets.iter() {
+        commands.entity(entity).insert(BodyId(counter.0));
+        counter.0 += 1;
+    }
+    for entity in newborns.iter() {
+        commands.entity(entity).insert(Lineage {
+            spawn_time: stats.sim_time,
+            merges: 0,
+        });
+    }
+}
+
+/// Orbital velocities scale with sqrt(g), rescaling them keeps circular orbits circular
+/// when `g` is changed during a run. Not possible when gravity changes its sign.
+fn rescale_on_g_change(
+    settings: Res<Settings>,
+    mut planets: Query<&mut Velocity>,
+    mut last_g: Local<Option<f32>>,
+) {
+    let previous = last_g.replace(settings.g);
+    let previous = match previous {
+        Some(previous) if previous != settings.g => previous,
+        _ => return,
+    };
+    if !settings.rescale_on_g_change || previous * settings.g <= 0.0 {
+        return;
+    }
+    let factor = (settings.g / previous).sqrt();
+    for mut velocity in planets.iter_mut() {
+        velocity.0 *= factor;
+    }
+}
+
+/// Merged planet of a collision fast enough to eject debris.
+struct Impact {
+    transform: Transform,
+    velocity: Vec2,
+    /// Relative speed of the colliding planets
+    speed: f32,
+    radius: f32,
+}
+
+/// Ejects equal fragments with total `mass` evenly spread around the merged planet,
+/// so their ejection momenta cancel out and the total momentum is preserved.
+fn spawn_debris(
+    commands: &mut Commands,
+    settings: &Settings,
+    source: &Planet,
+    mass: f32,
+    impact: &Impact,
+    is_comparison: bool,
+) {
+    let count = settings.debris_count.max(2);
+    let fragment_radius = physics::volume_to_radius(mass / count as f32 / source.density);
+    let ejection_speed = DEBRIS_SPEED_FRACTION * impact.speed;
+    let first_angle = impact.velocity.y.atan2(impact.velocity.x);
+    for k in 0..count {
+        let angle = first_angle + 2.0 * PI * k as f32 / count as f32;
+        let direction = Vec2::new(angle.cos(), angle.sin());
+        // start outside the merged planet so the fragments don't merge back immediately
+        let offset = direction * (impact.radius + 2.0 * fragment_radius);
+        let mut transform = impact.transform;
+        transform.translation.x += offset.x;
+        transform.translation.y += offset.y;
+        let fragment = Planet {
+            radius: fragment_radius,
+            ..source.clone()
+        };
+        let fragment = spawn_planet(
+            commands,
+            fragment,
+            Velocity(impact.velocity + direction * ejection_speed),
+            transform,
+        );
+        if is_comparison {
+            commands.entity(fragment).insert(Comparison);
+        }
+    }
+}
+
+/// `scenario` is the one the planets were built with, see `Scenario::allows_collisions`.
+fn force_params(settings: &Settings, scenario: Option<Scenario>) -> ForceParams {
+    ForceParams {
+        g: settings.g,
+        collisions: settings.collisions && scenario.map_or(true, Scenario::allows_collisions),
+        merge_policy: settings.merge_policy,
+        gr_correction: settings.gr_correction,
+        merge_overlap: settings.merge_overlap,
+        test_particle_mass: settings
+            .test_particle_mode
+            .then(|| settings.test_particle_mass),
+        softening_mode: settings.softening_mode,
+        softening: settings.softening,
+        collision_grid: settings.collision_grid,
+    }
+}
+
+/// Position followed by "Center on the largest", the planet with the largest radius.
+/// Without planets it falls back to the most massive sun and then to the barycenter.
+fn largest_planet_position(bodies: &[Body]) -> Vec2 {
+    let largest = |a: &&Body, b: &&Body| a.radius.total_cmp(&b.radius);
+    let heaviest = |a: &&Body, b: &&Body| a.mass.total_cmp(&b.mass);
+    bodies
+        .iter()
+        .filter(|body| !body.is_sun)
+        .max_by(largest)
+        .or_else(|| bodies.iter().filter(|body| body.is_sun).max_by(heaviest))
+        .map(|body| body.position)
+        .unwrap_or_else(|| physics::system_totals(bodies).barycenter)
+}
+
+/// Mass of the n-th most massive body, zero when there are fewer bodies than `n`.
+fn nth_largest_mass(bodies: &[Body], n: usize) -> f32 {
+    let mut masses: Vec<f32> = bodies.iter().map(|body| body.mass).collect();
+    masses.sort_by(|a, b| b.total_cmp(a));
+    masses.get(n.saturating_sub(1)).copied().unwrap_or(0.0)
+}
+
+fn limit_bodies(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    planets: Query<(Entity, &Planet, Option<&BodyId>), (Without<Anchor>, Without<Comparison>)>,
+    copies: Query<(Entity, &BodyId), With<Comparison>>,
+) {
+    let count = planets.iter().count();
+    if count <= settings.max_bodies {
+        return;
+    }
+    let candidates = lightest_planets(&planets, count - settings.max_bodies);
+    despawn_with_copies(&mut commands, &candidates, &copies);
+}
+
+/// The `n` least massive planets of the main universe which aren't suns
+fn lightest_planets(
+    planets: &Query<(Entity, &Planet, Option<&BodyId>), (Without<Anchor>, Without<Comparison>)>,
+    n: usize,
+) -> Vec<(Entity, Option<BodyId>)> {
+    let mut candidates: Vec<(Entity, Option<BodyId>, f32)> = planets
+        .iter()
+        .filter(|(_, planet, _)| !planet.is_sun)
+        .map(|(entity, planet, id)| (entity, id.copied(), planet.mass()))
+        .collect();
+    candidates.sort_by(|a, b| a.2.total_cmp(&b.2));
+    candidates
+        .into_iter()
+        .take(n)
+        .map(|(entity, id, _)| (entity, id))
+        .collect()
+}
+
+/// Despawns the planets together with their copies in the comparison universe, so both
+/// universes keep the same bodies
+fn despawn_with_copies(
+    commands: &mut Commands,
+    planets: &[(Entity, Option<BodyId>)],
+    copies: &Query<(Entity, &BodyId), With<Comparison>>,
+) {
+    let mut ids = HashSet::new();
+    for (entity, id) in planets {
+        commands.entity(*entity).despawn();
+        ids.extend(*id);
+    }
+    for (copy, id) in copies.iter() {
+        if ids.contains(id) {
+            commands.entity(copy).despawn();
+        }
+    }
+}
+
+/// Removes the least massive planets when the measured frame rate stays too low, and
+/// optionally spawns new ones while it stays above the target.
+#[allow(clippy::too_many_arguments)]
+fn auto_scale_bodies(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    stats: Res<Stats>,
+    diagnostics: Res<Diagnostics>,
+    time: Res<Time>,
+    planets: Query<(Entity, &Planet, Option<&BodyId>), (Without<Anchor>, Without<Comparison>)>,
+    copies: Query<(Entity, &BodyId), With<Comparison>>,
+    (mut slow_since, mut fast_since): (Local<Option<f64>>, Local<Option<f64>>),
+) {
+    let fps = diagnostics
+        .get(FrameTimeDiagnosticsPlugin::FPS)
+        .and_then(|fps| fps.average())
+        .filter(|_| settings.auto_scale_bodies);
+    let now = time.seconds_since_startup();
+    match fps {
+        Some(fps) if (fps as f32) < settings.auto_scale_fps => {
+            *fast_since = None;
+            let since = *slow_since.get_or_insert(now);
+            if now - since < AUTO_SCALE_DELAY {
+                return;
+            }
+            // wait for a fresh average before removing more
+            *slow_since = None;
+            let count = planets.iter().count();
+            let n_remove = ((count as f32 * AUTO_SCALE_FRACTION).ceil() as usize)
+                .min(count.saturating_sub(settings.auto_scale_min_bodies));
+            if n_remove == 0 {
+                return;
+            }
+            let candidates = lightest_planets(&planets, n_remove);
+            despawn_with_copies(&mut commands, &candidates, &copies);
+            info!(
+                "{:.1} FPS is below the target of {}, removed {} smallest planets",
+                fps, settings.auto_scale_fps, n_remove
+            );
+        }
+        // the new planets would only exist in the main universe of a comparison
+        Some(fps)
+            if settings.auto_scale_respawn
+                && copies.is_empty()
+                && (fps as f32) > settings.auto_scale_fps + AUTO_SCALE_MARGIN =>
+        {
+            *slow_since = None;
+            let since = *fast_since.get_or_insert(now);
+            if now - since < AUTO_SCALE_DELAY {
+                return;
+            }
+            // wait for a fresh average before adding more
+            *fast_since = None;
+            let count = planets.iter().count();
+            let n_spawn = ((count as f32 * AUTO_SCALE_FRACTION).ceil() as usize)
+                .max(1)
+                .min(settings.auto_scale_max_bodies.saturating_sub(count));
+            if n_spawn == 0 {
+                return;
+            }
+            let sun = match largest_sun(planets.iter().map(|(_, planet, _)| (planet, ()))) {
+                Some((sun, _)) => sun.clone(),
+                None => return,
+            };
+            // a different stream than the initial scene so the new orbits don't repeat it
+            let mut rng = StdRng::seed_from_u64(settings.seed ^ stats.frame_number as u64);
+            spawn_many_orbits(&mut commands, &settings, &sun, n_spawn, &mut rng);
+            info!(
+                "{:.1} FPS is above the target of {}, spawned {} new planets",
+                fps, settings.auto_scale_fps, n_spawn
+            );
+        }
+        _ => {
+            *slow_since = None;
+            *fast_since = None;
+        }
+    }
+}
+
+fn place_anchor(
+    mut commands: Commands,
+    mut egui_ctx: ResMut<EguiContext>,
+    windows: Res<Windows>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    camera: Query<(&Transform, &OrthographicProjection), With<PanCam>>,
+    settings: Res<Settings>,
+    stats: Res<Stats>,
+) {
+    if !mouse_buttons.just_pressed(MouseButton::Left)
+        || !stats.place_anchors
+        || egui_ctx.ctx_mut().wants_pointer_input()
+        || egui_ctx.ctx_mut().is_pointer_over_area()
+    {
+        return;
+    }
+    let (window, (camera_transform, projection)) =
+        match (windows.get_primary(), camera.iter().next()) {
+            (Some(window), Some(camera)) => (window, camera),
+            _ => return,
+        };
+    let cursor = match cursor_to_world(window, camera_transform, projection) {
+        Some(cursor) => cursor,
+        None => return,
+    };
+    let anchor = Planet {
+        radius: ANCHOR_RADIUS,
+        density: settings.anchor_mass / physics::radius_to_volume(ANCHOR_RADIUS),
+        color: Color::PURPLE,
+        is_sun: false,
+    };
+    let entity = spawn_planet(
+        &mut commands,
+        anchor,
+        Velocity(Vec2::ZERO),
+        Transform::from_xyz(cursor.x, cursor.y, layers::PLANETS),
+    );
+    commands.entity(entity).insert(Anchor);
+}
+
+fn track_cursor(
+    mut stats: ResMut<Stats>,
+    windows: Res<Windows>,
+    camera: Query<(&Transform, &OrthographicProjection), With<PanCam>>,
+) {
+    stats.cursor_position = match (windows.get_primary(), camera.iter().next()) {
+        (Some(window), Some((transform, projection))) => {
+            cursor_to_world(window, transform, projection)
+        }
+        _ => None,
+    };
+}
+
+/// Starts moving the camera to the collision which paused the simulation, keeping the zoom.
+fn focus_collision(
+    mut stats: ResMut<Stats>,
+    mut camera: Query<(&mut PanCam, &OrthographicProjection)>,
+) {
+    if !std::mem::take(&mut stats.focus_collision_pending) {
+        return;
+    }
+    let collision = match stats.paused_collision {
+        Some(collision) => collision,
+        None => return,
+    };
+    for (mut pancam, projection) in camera.iter_mut() {
+        pancam.focus(collision.position, projection.scale);
+    }
+    stats.center_on_largest = false;
+}
+
+/// Starts moving the camera to the selected planet, zoomed so it fills `FOCUS_FRACTION`
+/// of the view.
+fn focus_selected(
+    mut stats: ResMut<Stats>,
+    selected: Query<(&Planet, &Transform), With<Selected>>,
+    mut camera: Query<(&mut PanCam, &OrthographicProjection)>,
+) {
+    if !stats.focus_selected_pending {
+        return;
+    }
+    stats.focus_selected_pending = false;
+    let (planet, transform) = match selected.iter().next() {
+        Some(selected) => selected,
+        None => return,
+    };
+    for (mut pancam, projection) in camera.iter_mut() {
+        let half_view = projection.top.min(projection.right);
+        pancam.focus(
+            transform.translation.truncate(),
+            planet.radius / (FOCUS_FRACTION * half_view),
+        );
+    }
+    stats.center_on_largest = false;
+}
+
+fn select_planet(
+    mut commands: Commands,
+    mut egui_ctx: ResMut<EguiContext>,
+    windows: Res<Windows>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    camera: Query<(&Transform, &OrthographicProjection), With<PanCam>>,
+    planets: Query<(Entity, &Planet, &Transform)>,
+    selected: Query<Entity, With<Selected>>,
+    partners: Query<Entity, With<BinaryPartner>>,
+    stats: Res<Stats>,
+) {
+    if !mouse_buttons.just_pressed(MouseButton::Left)
+        || stats.place_anchors
+        || egui_ctx.ctx_mut().wants_pointer_input()
+        || egui_ctx.ctx_mut().is_pointer_over_area()
+    {
+        return;
+    }
+    let (window, (camera_transform, projection)) =
+        match (windows.get_primary(), camera.iter().next()) {
+            (Some(window), Some(camera)) => (window, camera),
+            _ => return,
+        };
+    let cursor = match cursor_to_world(window, camera_transform, projection) {
+        Some(cursor) => cursor,
+        None => return,
+    };
+
+    if let Some(entity) = planet_at(planets.iter(), cursor, projection) {
+        // shift+click pins the binary partner of the selected planet instead
+        if keys.pressed(KeyCode::LShift) || keys.pressed(KeyCode::RShift) {
+            for previous in partners.iter() {
+                commands.entity(previous).remove::<BinaryPartner>();
+            }
+            commands.entity(entity).insert(BinaryPartner);
+        } else {
+            for previous in selected.iter() {
+                commands.entity(previous).remove::<Selected>();
+            }
+            commands.entity(entity).insert(Selected);
+        }
+    }
+}
+
+/// Planet whose edge is closest to `cursor`, within `SELECT_TOLERANCE_PX` pixels.
+fn planet_at<'a>(
+    planets: impl Iterator<Item = (Entity, &'a Planet, &'a Transform)>,
+    cursor: Vec2,
+    projection: &OrthographicProjection,
+) -> Option<Entity> {
+    let tolerance = SELECT_TOLERANCE_PX * projection.scale;
+    planets
+        .map(|(entity, planet, transform)| {
+            let distance = transform.translation.truncate().distance(cursor) - planet.radius;
+            (entity, distance)
+        })
+        .filter(|(_, distance)| *distance < tolerance)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(entity, _)| entity)
+}
+
+/// Adds `Settings.nudge_speed` to the velocity of the planet under the cursor in the
+/// direction of the pressed arrow key.
+fn nudge_hovered_planet(
+    mut egui_ctx: ResMut<EguiContext>,
+    keys: Res<Input<KeyCode>>,
+    settings: Res<Settings>,
+    mut stats: ResMut<Stats>,
+    camera: Query<&OrthographicProjection, With<PanCam>>,
+    mut planets: Query<(Entity, &Planet, &Transform, &mut Velocity)>,
+) {
+    if egui_ctx.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    let directions = [
+        (KeyCode::Left, -Vec2::X),
+        (KeyCode::Right, Vec2::X),
+        (KeyCode::Up, Vec2::Y),
+        (KeyCode::Down, -Vec2::Y),
+    ];
+    let nudge = directions
+        .iter()
+        .filter(|(key, _)| keys.just_pressed(*key))
+        .fold(Vec2::ZERO, |sum, (_, direction)| sum + *direction);
+    if nudge == Vec2::ZERO {
+        return;
+    }
+    let (cursor, projection) = match (stats.cursor_position, camera.iter().next()) {
+        (Some(cursor), Some(projection)) => (cursor, projection),
+        _ => return,
+    };
+    let hovered = planet_at(
+        planets
+            .iter()
+            .map(|(entity, planet, transform, _)| (entity, planet, transform)),
+        cursor,
+        projection,
+    );
+    if let Some(entity) = hovered {
+        if let Ok((_, _, _, mut velocity)) = planets.get_mut(entity) {
+            velocity.0 += nudge * settings.nudge_speed;
+            stats.reference_energy = None;
+        }
+    }
+}
+
+/// Reddish version of a color marking the planets of the comparison scene.
+fn comparison_tint(color: Color) -> Color {
+    Color::rgba(
+        (color.r() + 1.0) / 2.0,
+        color.g() / 2.0,
+        color.b() / 2.0,
+        color.a(),
+    )
+}
+
+/// Mass weighted average of two colors, computed in linear RGB.
+fn blend_colors(color_1: Color, mass_1: f32, color_2: Color, mass_2: f32) -> Color {
+    let weight = mass_2 / (mass_1 + mass_2);
+    let linear_1 = color_1.as_linear_rgba_f32();
+    let linear_2 = color_2.as_linear_rgba_f32();
+    let mix = |i: usize| linear_1[i] * (1.0 - weight) + linear_2[i] * weight;
+    Color::rgba_linear(mix(0), mix(1), mix(2), mix(3))
+}
+
+fn merge_planets(planet_1: &Planet, planet_2: &Planet, settings: &Settings) -> Planet {
+    let new_radius =
+        physics::merge_radius(planet_1.radius, planet_2.radius, settings.merge_dimension);
+    let color = if !settings.blend_colors_on_merge {
+        planet_1.color
+    } else if planet_1.is_sun != planet_2.is_sun {
+        // suns keep their color when swallowing planets
+        if planet_1.is_sun {
+            planet_1.color
+        } else {
+            planet_2.color
+        }
+    } else {
+        blend_colors(
+            planet_1.color,
+            planet_1.mass(),
+            planet_2.color,
+            planet_2.mass(),
+        )
+    };
+    // density is chosen so that the total mass is preserved regardless of how the radius grows
+    Planet {
+        radius: new_radius,
+        density: (planet_1.mass() + planet_2.mass()) / physics::radius_to_volume(new_radius),
+        color,
+        is_sun: planet_1.is_sun || planet_2.is_sun,
+    }
+}
+
+fn despawn_traces(
+    mut ev_clear_trace: EventReader<ClearTraces>,
+    mut commands: Commands,
+    traces: Query<(Entity, &Trace)>,
+    time: Res<Time>,
+) {
+    let mut manual_clear = false;
+    for _ in ev_clear_trace.iter() {
+        manual_clear = true;
+    }
+    for (entity, trace) in traces.iter() {
+        if trace.live_until < time.seconds_since_startup() || manual_clear {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn fade_traces(
+    stats: Res<Stats>,
+    time: Res<Time>,
+    mut traces: Query<(&Trace, &mut Sprite, &mut Transform)>,
+) {
+    let now = time.seconds_since_startup();
+    let layer = trace_layer(&stats);
+    for (trace, mut sprite, mut transform) in traces.iter_mut() {
+        // move the existing traces as well when the order is changed
+        if transform.translation.z != layer {
+            transform.translation.z = layer;
+        }
+        sprite.color.set_a(trace_alpha(&stats, trace, now));
+    }
+}
+
+/// Alpha of a trace point at `now` with the fade of `Stats.trace_fade`
+fn trace_alpha(stats: &Stats, trace: &Trace, now: f64) -> f32 {
+    let remaining = ((trace.live_until - now) / trace.lifetime).clamp(0.0, 1.0) as f32;
+    let alpha = match stats.trace_fade {
+        TraceFade::Off => 1.0,
+        TraceFade::Linear => remaining,
+        TraceFade::Exponential => (-TRACE_FADE_RATE * (1.0 - remaining)).exp(),
+    };
+    alpha * trace.opacity
+}
+
+fn animate_flashes(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut flashes: Query<(Entity, &Flash, &mut Transform, &mut DrawMode)>,
+) {
+    for (entity, flash, mut transform, mut draw_mode) in flashes.iter_mut() {
+        let progress = ((time.seconds_since_startup() - flash.spawned_at) / FLASH_DURATION) as f32;
+        if progress >= 1.0 {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        // the flash is a unit circle, its radius is animated through the scale
+        let radius = flash.max_radius * progress.max(0.05);
+        transform.scale = Vec3::new(radius, radius, 1.0);
+        if let DrawMode::Stroke(ref mut stroke_mode) = *draw_mode {
+            stroke_mode.color.set_a(1.0 - progress);
+        }
+    }
+}
+
+fn animate_merges(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut absorbing: Query<(Entity, &Absorbing, &mut Transform, &mut DrawMode)>,
+    targets: Query<&Transform, Without<Absorbing>>,
+) {
+    for (entity, absorbing, mut transform, mut draw_mode) in absorbing.iter_mut() {
+        let progress = ((time.seconds_since_startup() - absorbing.spawned_at)
+            / MERGE_ANIMATION_DURATION) as f32;
+        if progress >= 1.0 {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        // the merged planet only exists from the frame after the merge
+        if let Ok(target) = targets.get(absorbing.into) {
+            let position = absorbing.from.lerp(target.translation.truncate(), progress);
+            transform.translation.x = position.x;
+            transform.translation.y = position.y;
+        }
+        let scale = 1.0 - progress;
+        transform.scale = Vec3::new(scale, scale, 1.0);
+        if let DrawMode::Outlined {
+            ref mut fill_mode,
+            ref mut outline_mode,
+        } = *draw_mode
+        {
+            fill_mode.color.set_a(scale);
+            outline_mode.color.set_a(scale);
+        }
+    }
+}
+
+/// Starts a second copy of the program with the current planets as its scene, so both
+/// can run on with different settings. The settings, with the scene code of the planets,
+/// go through the standard input of the copy as JSON since the command line is too short
+/// for large systems. Both start from the same state until a setting is changed in one.
+#[cfg(not(target_arch = "wasm32"))]
+fn duplicate_simulation(
+    mut stats: ResMut<Stats>,
+    settings: Res<Settings>,
+    mut copies: Local<Vec<std::process::Child>>,
+    planets: Query<(&Planet, &Velocity, &Transform), (Without<Anchor>, Without<Comparison>)>,
+) {
+    // closed copies are waited for so they don't linger as zombies
+    copies.retain_mut(|copy| matches!(copy.try_wait(), Ok(None)));
+    if !std::mem::take(&mut stats.duplicate_pending) {
+        return;
+    }
+    let mut copy_settings = Settings {
+        scenario: Scenario::Scene,
+        scene_code: scene::encode(&current_scene(&settings, stats.scenario, planets.iter())),
+        scene_path: String::new(),
+        ..settings.clone()
+    };
+    // the copy would write into the same files
+    copy_settings.log_diagnostics = false;
+    copy_settings.log_merges = false;
+    let input = serde_json::to_string(&copy_settings).expect("settings are always serializable");
+    let started = std::env::current_exe()
+        .map_err(|err| format!("can't find the program to start: {}", err))
+        .and_then(|program| {
+            std::process::Command::new(program)
+                .args(["--scene", "-"])
+                .stdin(std::process::Stdio::piped())
+                .spawn()
+                .map_err(|err| format!("can't start a second simulation: {}", err))
+        });
+    match started {
+        Ok(mut copy) => {
+            let mut stdin = copy.stdin.take().unwrap();
+            // the copy reads its input while starting up, don't hold the frame for it
+            std::thread::spawn(move || {
+                use std::io::Write;
+                if let Err(err) = stdin.write_all(input.as_bytes()) {
+                    warn!("can't send the scene to the second simulation: {}", err);
+                }
+            });
+            copies.push(copy);
+        }
+        Err(err) => warn!("{}", err),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn apply_vsync(settings: Res<Settings>, mut windows: ResMut<Windows>) {
+    if let Some(window) = windows.get_primary_mut() {
+        if window.vsync() != settings.vsync {
+            window.set_vsync(settings.vsync);
+        }
+    }
+}
+
+fn apply_msaa(settings: Res<Settings>, mut msaa: ResMut<Msaa>) {
+    if msaa.samples != settings.msaa_samples {
+        msaa.samples = settings.msaa_samples;
+    }
+}
+
+/// Sleeps at the end of the frame so the frame rate doesn't exceed `Settings.target_fps`.
+#[cfg(not(target_arch = "wasm32"))]
+fn limit_frame_rate(settings: Res<Settings>, mut last_frame: Local<Option<std::time::Instant>>) {
+    if settings.target_fps > 0 {
+        if let Some(last_frame) = *last_frame {
+            let frame_time = std::time::Duration::from_secs_f64(1.0 / settings.target_fps as f64);
+            if let Some(remaining) = frame_time.checked_sub(last_frame.elapsed()) {
+                std::thread::sleep(remaining);
+            }
+        }
+    }
+    *last_frame = Some(std::time::Instant::now());
+}
+
+fn time_step_keys(
+    mut egui_ctx: ResMut<EguiContext>,
+    keys: Res<Input<KeyCode>>,
+    mut settings: ResMut<Settings>,
+    mut stats: ResMut<Stats>,
+    time: Res<Time>,
+) {
+    if egui_ctx.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    let factor = if keys.just_pressed(KeyCode::LBracket) {
+        0.5
+    } else if keys.just_pressed(KeyCode::RBracket) {
+        2.0
+    } else {
+        return;
+    };
+    settings.time_step = (settings.time_step * factor).clamp(MIN_TIME_STEP, MAX_TIME_STEP);
+    stats.time_step_notice_until = time.seconds_since_startup() + TIME_STEP_NOTICE_DURATION;
+}
+
+fn time_step_notice(
+    mut egui_context: ResMut<EguiContext>,
+    settings: Res<Settings>,
+    stats: Res<Stats>,
+    time: Res<Time>,
+) {
+    if time.seconds_since_startup() > stats.time_step_notice_until {
+        return;
+    }
+    egui::Area::new("time_step_notice")
+        .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 20.0))
+        .interactable(false)
+        .show(egui_context.ctx_mut(), |ui| {
+            ui.heading(format!("Time step {:.1}", settings.time_step));
+        });
+}
+
+/// The most massive planet marked as a sun, the launched planets orbit it.
+fn largest_sun<'a, T>(planets: impl Iterator<Item = (&'a Planet, T)>) -> Option<(&'a Planet, T)> {
+    planets
+        .filter(|(planet, _)| planet.is_sun)
+        .max_by(|(a, _), (b, _)| a.mass().total_cmp(&b.mass()))
+}
+
+/// Position and velocity relative to the sun of a planet placed at the periapsis
+/// of the orbit given in the settings.
+fn launch_state(settings: &Settings, sun_mass: f32) -> (Vec2, Vec2) {
+    let mu = settings.g * sun_mass;
+    let semi_major_axis = settings.launch_semi_major_axis;
+    let periapsis = semi_major_axis * (1.0 - settings.launch_eccentricity);
+    let speed = physics::vis_viva_speed(mu, periapsis, semi_major_axis);
+    // the velocity is perpendicular to the radius at the periapsis
+    let direction = Vec2::new(
+        settings.launch_periapsis_angle.to_radians().cos(),
+        settings.launch_periapsis_angle.to_radians().sin(),
+    );
+    (direction * periapsis, direction.perp() * speed)
+}
+
+/// Cools by the simulated time which passed since the last frame, nothing while paused.
+fn cool_planets(
+    settings: Res<Settings>,
+    stats: Res<Stats>,
+    replay: Res<Replay>,
+    mut last_sim_time: Local<f64>,
+    mut planets: Query<&mut Temperature>,
+) {
+    // a reset starts the simulated time from zero again
+    let elapsed = (stats.sim_time - *last_sim_time).max(0.0) as f32;
+    *last_sim_time = stats.sim_time;
+    if replay.is_playing() || elapsed == 0.0 {
+        return;
+    }
+    let cooling = (-settings.cooling_rate * elapsed).exp();
+    for mut temperature in planets.iter_mut() {
+        temperature.0 *= cooling;
+    }
+}
+
+fn stir_planets(
+    mut ev_stir: EventReader<Stir>,
+    settings: Res<Settings>,
+    mut stats: ResMut<Stats>,
+    mut planets: Query<&mut Velocity, Without<Anchor>>,
+) {
+    for _ in ev_stir.iter() {
+        // the kicks add energy the correction shouldn't take away again
+        stats.reference_energy = None;
+        // seeded so a run is reproducible as long as the stirs happen at the same frames
+        let mut rng = StdRng::seed_from_u64(settings.seed ^ stats.frame_number as u64);
+        for mut velocity in planets.iter_mut() {
+            let angle = rng.gen::<f32>() * 2.0 * PI;
+            let kick = rng.gen::<f32>() * settings.stir_strength;
+            velocity.0 += Vec2::new(angle.cos(), angle.sin()) * kick;
+        }
+    }
+}
+
+/// Snapshot of the planets and the settings kept in scenes. Anchors and the
+/// comparison copies are left out.
+fn current_scene<'a>(
+    settings: &Settings,
+    scenario: Option<Scenario>,
+    planets: impl Iterator<Item = (&'a Planet, &'a Velocity, &'a Transform)>,
+) -> scene::Scene {
+    let bodies = planets
+        .map(|(planet, velocity, transform)| {
+            let [r, g, b, _] = planet.color.as_rgba_f32();
+            scene::SceneBody {
+                position: transform.translation.truncate().into(),
+                velocity: velocity.0.into(),
+                radius: planet.radius,
+                density: planet.density,
+                color: [r, g, b],
+                is_sun: planet.is_sun,
+            }
+        })
+        .collect();
+    scene::Scene {
+        settings: scene::SceneSettings {
+            g: Some(settings.g),
+            time_step: Some(settings.time_step),
+            collisions: Some(force_params(settings, scenario).collisions),
+        },
+        bodies,
+    }
+}
+
+/// Encodes the planets, without the anchors and the comparison copies, as a scene code and
+/// copies it to the clipboard. It is shown in the settings as well to copy it by hand.
+fn copy_scene(
+    mut ev_copy_scene: EventReader<CopyScene>,
+    #[cfg(not(target_arch = "wasm32"))] mut egui_context: ResMut<EguiContext>,
+    mut stats: ResMut<Stats>,
+    settings: Res<Settings>,
+    planets: Query<(&Planet, &Velocity, &Transform), (Without<Anchor>, Without<Comparison>)>,
+) {
+    if ev_copy_scene.iter().count() == 0 {
+        return;
+    }
+    let code = scene::encode(&current_scene(&settings, stats.scenario, planets.iter()));
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        egui_context.ctx_mut().output().copied_text = code.clone();
+    }
+    // egui only fills the system clipboard on native
+    #[cfg(target_arch = "wasm32")]
+    clipboard::write_text(&code);
+    stats.scene_code = code;
+}
+
+/// Starts the scene of the scene code field. The web build first fills the field from the
+/// browser clipboard, which answers a few frames after the request.
+fn paste_scene(
+    mut ev_paste_scene: EventReader<PasteScene>,
+    mut ev_reset: EventWriter<Reset>,
+    mut settings: ResMut<Settings>,
+    mut stats: ResMut<Stats>,
+    #[cfg(target_arch = "wasm32")] inbox: Res<clipboard::ClipboardInbox>,
+) {
+    let requested = ev_paste_scene.iter().count() > 0;
+    #[cfg(target_arch = "wasm32")]
+    let code = {
+        if requested {
+            clipboard::request_text(&inbox);
+        }
+        match inbox.take() {
+            Some(text) => text,
+            None => return,
+        }
+    };
+    #[cfg(not(target_arch = "wasm32"))]
+    let code = {
+        if !requested {
+            return;
+        }
+        stats.scene_code.clone()
+    };
+    match scene::decode(&code) {
+        Ok(_) => {
+            settings.scenario = Scenario::Scene;
+            settings.scene_code = code.trim().to_string();
+            ev_reset.send(Reset);
+        }
+        Err(err) => warn!("{}", err),
+    }
+    stats.scene_code = code;
+}
+
+/// Places a planet at the periapsis of the orbit given in the settings around the largest sun.
+fn launch_planet(
+    mut commands: Commands,
+    mut ev_launch: EventReader<Launch>,
+    settings: Res<Settings>,
+    planets: Query<(&Planet, &Transform, &Velocity)>,
+) {
+    for _ in ev_launch.iter() {
+        let sun = largest_sun(
+            planets
+                .iter()
+                .map(|(planet, transform, velocity)| (planet, (transform, velocity))),
+        );
+        let (sun, (sun_transform, sun_velocity)) = match sun {
+            Some(sun) => sun,
+            None => {
+                warn!("there is no sun to launch a planet around");
+                continue;
+            }
+        };
+        let (position, velocity) = launch_state(&settings, sun.mass());
+        let position = sun_transform.translation.truncate() + position;
+        let planet = Planet {
+            radius: settings.max_planet_size,
+            density: settings.max_planet_density,
+            color: Color::WHITE,
+            is_sun: false,
+        };
+        spawn_planet(
+            &mut commands,
+            planet,
+            Velocity(**sun_velocity + velocity),
+            Transform::from_xyz(position.x, position.y, layers::PLANETS),
+        );
+    }
+}
+
+/// Spawns new planets on fresh orbits around the largest sun or removes the least massive
+/// ones, the rest of the system is left as it is.
+fn change_planet_count(
+    mut commands: Commands,
+    mut ev_change: EventReader<ChangePlanetCount>,
+    mut settings: ResMut<Settings>,
+    stats: Res<Stats>,
+    planets: Query<(Entity, &Planet), Without<Anchor>>,
+) {
+    for ChangePlanetCount(delta) in ev_change.iter() {
+        if *delta > 0 {
+            let sun = match largest_sun(planets.iter().map(|(_, planet)| (planet, ()))) {
+                Some((sun, _)) => sun.clone(),
+                None => {
+                    warn!("there is no sun to add planets around");
+                    continue;
+                }
+            };
+            // a different stream than the initial scene so the new orbits don't repeat it
+            let mut rng = StdRng::seed_from_u64(settings.seed ^ stats.frame_number as u64);
+            spawn_many_orbits(&mut commands, &settings, &sun, *delta as usize, &mut rng);
+            settings.n_objects += *delta as usize;
+        } else {
+            let n_remove = delta.unsigned_abs() as usize;
+            let mut candidates: Vec<(Entity, f32)> = planets
+                .iter()
+                .filter(|(_, planet)| !planet.is_sun)
+                .map(|(entity, planet)| (entity, planet.mass()))
+                .collect();
+            candidates.sort_by(|a, b| a.1.total_cmp(&b.1));
+            for (entity, _) in candidates.iter().take(n_remove) {
+                commands.entity(*entity).despawn();
+            }
+            settings.n_objects = settings.n_objects.saturating_sub(n_remove);
+        }
+    }
+}
+
+/// Path of a test particle launched with the current settings, relative to the sun,
+/// integrated with the same functions as the simulation for at most one period.
+fn predict_orbit(settings: &Settings, sun: &Planet) -> Vec<Vec2> {
+    let (position, velocity) = launch_state(settings, sun.mass());
+    let mut bodies = [
+        Body {
+            position: Vec2::ZERO,
+            velocity: Vec2::ZERO,
+            mass: sun.mass(),
+            radius: sun.radius,
+            fixed: false,
+            is_sun: true,
+        },
+        // massless so it doesn't pull the sun
+        Body {
+            position,
+            velocity,
+            mass: 0.0,
+            radius: settings.max_planet_size,
+            fixed: false,
+            is_sun: false,
+        },
+    ];
+    let params = ForceParams {
+        g: settings.g,
+        collisions: false,
+        merge_policy: settings.merge_policy,
+        gr_correction: settings.gr_correction,
+        merge_overlap: settings.merge_overlap,
+        test_particle_mass: None,
+        softening_mode: settings.softening_mode,
+        softening: settings.softening,
+        collision_grid: false,
+    };
+    let dt = 1.0 / settings.time_step;
+    let mu = settings.g * sun.mass();
+    let semi_major_axis = settings.launch_semi_major_axis;
+    let period = 2.0 * PI * (semi_major_axis.powf(3.0) / mu).sqrt();
+    let steps = if period.is_finite() {
+        ((period / dt) as usize).min(MAX_PREVIEW_STEPS)
+    } else {
+        MAX_PREVIEW_STEPS
+    };
+    let sample_every = (steps / PREVIEW_POINTS).max(1);
+
+    let mut path = vec![position];
+    for step in 1..=steps {
+        let forces = physics::compute_forces(&bodies, &params);
+        physics::integrate(&mut bodies, &forces, dt);
+        if step % sample_every == 0 {
+            path.push(bodies[1].position - bodies[0].position);
+        }
+    }
+    path
+}
+
+#[derive(Component)]
+struct LaunchPreview;
+
+#[derive(Component)]
+struct PredictedPaths;
+
+/// Runs a copy of the system forward and draws the paths of all the planets. They stay
+/// until the traces are cleared or the simulation is restarted.
+fn predict_paths(
+    mut commands: Commands,
+    mut ev_predict: EventReader<Predict>,
+    mut ev_clear_traces: EventReader<ClearTraces>,
+    mut ev_reset: EventReader<Reset>,
+    settings: Res<Settings>,
+    stats: Res<Stats>,
+    planets: Query<(Entity, &Planet, &Velocity, &Transform), Without<Comparison>>,
+    anchors: Query<(), With<Anchor>>,
+    body_ids: Query<&BodyId>,
+    drawn: Query<Entity, With<PredictedPaths>>,
+) {
+    let predict = ev_predict.iter().count() > 0;
+    let clear = ev_clear_traces.iter().count() > 0 || ev_reset.iter().count() > 0;
+    if !predict && !clear {
+        return;
+    }
+    for entity in drawn.iter() {
+        commands.entity(entity).despawn();
+    }
+    if !predict {
+        return;
+    }
+
+    let mut planets: Vec<_> = planets.iter().collect();
+    // same order as in `gravity` so the same collisions are found
+    planets.sort_by_key(|(entity, _, _, _)| body_order(&body_ids, *entity));
+    let bodies: Vec<Body> = planets
+        .iter()
+        .map(|(entity, planet, velocity, transform)| Body {
+            position: transform.translation.truncate(),
+            velocity: velocity.0,
+            mass: planet.mass(),
+            radius: planet.radius,
+            fixed: anchors.get(*entity).is_ok(),
+            is_sun: planet.is_sun,
+        })
+        .collect();
+    let steps = settings.prediction_steps;
+    let paths = physics::predict_paths(
+        &bodies,
+        &force_params(&settings, stats.scenario),
+        1.0 / settings.time_step,
+        steps,
+        (steps / PREVIEW_POINTS).max(1),
+        settings.merge_dimension,
+    );
+
+    let mut builder = PathBuilder::new();
+    for path in paths.iter().filter(|path| path.len() > 1) {
+        builder.move_to(path[0]);
+        for point in path[1..].iter() {
+            builder.line_to(*point);
+        }
+    }
+    commands
+        .spawn_bundle(GeometryBuilder::build_as(
+            &builder.build(),
+            DrawMode::Stroke(StrokeMode::new(Color::rgba(1.0, 1.0, 1.0, 0.25), 1.0)),
+            Transform::from_xyz(0.0, 0.0, layers::OVERLAYS),
+        ))
+        .insert(PredictedPaths);
+}
+
+/// Draws the predicted path of the planet the "Launch" button would place as a dashed line.
+/// The path is recomputed only when the settings it depends on change and follows the sun.
+fn preview_launch(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    planets: Query<(&Planet, &Transform), Without<LaunchPreview>>,
+    mut preview: Query<(Entity, &mut Transform), With<LaunchPreview>>,
+    mut last_key: Local<Option<[f32; 7]>>,
+) {
+    let sun = largest_sun(planets.iter()).filter(|_| settings.launch_preview);
+    let key = sun.map(|(sun, _)| {
+        [
+            sun.mass(),
+            sun.radius,
+            settings.launch_semi_major_axis,
+            settings.launch_eccentricity,
+            settings.launch_periapsis_angle,
+            settings.g,
+            settings.time_step,
+        ]
+    });
+    if *last_key != key {
+        *last_key = key;
+        for (entity, _) in preview.iter() {
+            commands.entity(entity).despawn();
+        }
+        if let Some((sun, sun_transform)) = sun {
+            let path = predict_orbit(&settings, sun);
+            let mut builder = PathBuilder::new();
+            for (i, dash) in path.windows(2).enumerate() {
+                if i % 2 == 0 {
+                    builder.move_to(dash[0]);
+                    builder.line_to(dash[1]);
+                }
+            }
+            let translation = sun_transform.translation.truncate();
+            commands
+                .spawn_bundle(GeometryBuilder::build_as(
+                    &builder.build(),
+                    DrawMode::Stroke(StrokeMode::new(Color::rgba(1.0, 1.0, 1.0, 0.5), 1.0)),
+                    Transform::from_xyz(translation.x, translation.y, layers::OVERLAYS),
+                ))
+                .insert(LaunchPreview);
+        }
+    } else if let Some((_, sun_transform)) = sun {
+        for (_, mut transform) in preview.iter_mut() {
+            transform.translation.x = sun_transform.translation.x;
+            transform.translation.y = sun_transform.translation.y;
+        }
+    }
+}
+
+fn setup(mut commands: Commands, mut ev_reset: EventWriter<Reset>) {
+    commands
         .spawn_bundle(OrthographicCameraBundle::new_2d())
         .insert(PanCam::default())
         .insert(FlyCamera2d::default());
@@ -241,7 +2924,9 @@ fn setup(mut commands: Commands, mut ev_reset: EventWriter<Reset>) {
 fn setup_many_orbits(
     mut planet_query: Query<(Entity, &mut Planet)>,
     mut ev_reset: EventReader<Reset>,
-    settings: Res<Settings>,
+    mut settings: ResMut<Settings>,
+    mut stats: ResMut<Stats>,
+    mut merge_log: ResMut<MergeLog>,
     mut commands: Commands,
 ) {
     let mut manual_reset = false;
@@ -249,57 +2934,385 @@ fn setup_many_orbits(
         manual_reset = true;
     }
     if manual_reset {
+        stats.ejected_count = 0;
+        stats.collision_count = 0;
+        stats.paused_collision = None;
+        stats.initial_angular_momentum = None;
+        stats.reference_energy = None;
+        stats.scenario = Some(settings.scenario);
+        stats.integrator_start_pending = true;
+        stats.sim_time = 0.0;
+        merge_log.0.clear();
+        stats.accretion_rate = 0.0;
+        stats.body_decline_rate = 0.0;
+        stats.accretion_history.clear();
+        stats.comparison_pending = settings.compare_integrators;
         for (ent, _) in planet_query.iter() {
             commands.entity(ent).despawn();
         }
 
-        let mut rng = rand::thread_rng();
-        let sun = Planet {
-            radius: settings.sun_size,
-            density: settings.sun_density,
-            color: Color::YELLOW,
-            is_sun: true,
+        let mut rng = StdRng::seed_from_u64(settings.seed);
+        match settings.scenario {
+            Scenario::ManyOrbits => {
+                let sun = spawn_sun(&mut commands, &settings);
+                spawn_many_orbits(&mut commands, &settings, &sun, settings.n_objects, &mut rng);
+            }
+            Scenario::Ring => {
+                let sun = spawn_sun(&mut commands, &settings);
+                spawn_ring(&mut commands, &settings, &sun, &mut rng);
+            }
+            Scenario::Belt => {
+                let sun = spawn_sun(&mut commands, &settings);
+                spawn_annulus(
+                    &mut commands,
+                    &settings,
+                    settings.g * sun.mass(),
+                    (settings.belt_inner_radius, settings.belt_outer_radius),
+                    settings.belt_particles,
+                    settings.belt_dispersion,
+                    &mut rng,
+                );
+            }
+            Scenario::Ephemeris => {
+                spawn_ephemeris(&mut commands, &settings);
+            }
+            Scenario::Scene => spawn_scene(&mut commands, &mut settings),
+            Scenario::Resonances => {
+                let sun = spawn_sun(&mut commands, &settings);
+                spawn_resonances(&mut commands, &settings, &sun, &mut rng);
+            }
+        }
+    }
+}
+
+fn spawn_sun(commands: &mut Commands, settings: &Settings) -> Planet {
+    let sun = Planet {
+        radius: settings.sun_size,
+        density: settings.sun_density,
+        color: Color::YELLOW,
+        is_sun: true,
+    };
+    spawn_planet(
+        commands,
+        sun.clone(),
+        Velocity(Vec2::new(0.0, 0.0)),
+        Transform::from_xyz(0.0, 0.0, layers::PLANETS),
+    );
+    sun
+}
+
+fn ephemeris_scaling(settings: &Settings) -> ephemeris::Scaling {
+    ephemeris::Scaling {
+        distance: settings.ephemeris_distance_scale,
+        mass: settings.ephemeris_mass_scale,
+        g: settings.g,
+    }
+}
+
+/// Factor and unit the simulated time is shown in. The ephemeris scenario counts in years of
+/// real motion, the other ones use `sim_time_scale` and `sim_time_unit` of the settings.
+fn time_unit(settings: &Settings, scenario: Option<Scenario>) -> (f32, &str) {
+    match scenario {
+        Some(Scenario::Ephemeris) => (ephemeris_scaling(settings).years_per_time_unit(), "years"),
+        _ => (settings.sim_time_scale, &settings.sim_time_unit),
+    }
+}
+
+fn spawn_ephemeris(commands: &mut Commands, settings: &Settings) {
+    let bodies = match ephemeris::load(&settings.ephemeris_path) {
+        Ok(bodies) => bodies,
+        Err(err) => {
+            error!("{}", err);
+            return;
+        }
+    };
+    let scaling = ephemeris_scaling(settings);
+    for body in bodies {
+        let position = body.scaled_position(&scaling);
+        let planet = Planet {
+            radius: body.radius,
+            density: body.scaled_mass(&scaling) / physics::radius_to_volume(body.radius),
+            color: Color::rgb(body.color[0], body.color[1], body.color[2]),
+            is_sun: body.is_sun,
+        };
+        let entity = spawn_planet(
+            commands,
+            planet,
+            Velocity(body.scaled_velocity(&scaling)),
+            Transform::from_xyz(position.x, position.y, layers::PLANETS),
+        );
+        commands.entity(entity).insert(Name::new(body.name));
+    }
+}
+
+fn spawn_scene(commands: &mut Commands, settings: &mut Settings) {
+    let scene = if settings.scene_code.is_empty() {
+        scene::load(&settings.scene_path)
+    } else {
+        scene::decode(&settings.scene_code)
+    };
+    let scene = match scene {
+        Ok(scene) => scene,
+        Err(err) => {
+            error!("{}", err);
+            return;
+        }
+    };
+    if let Some(g) = scene.settings.g {
+        settings.g = g;
+    }
+    if let Some(time_step) = scene.settings.time_step {
+        settings.time_step = time_step;
+    }
+    if let Some(collisions) = scene.settings.collisions {
+        settings.collisions = collisions;
+    }
+    for body in scene.bodies {
+        let planet = Planet {
+            radius: body.radius,
+            density: body.density,
+            color: Color::rgb(body.color[0], body.color[1], body.color[2]),
+            is_sun: body.is_sun,
         };
         spawn_planet(
-            &mut commands,
-            sun.clone(),
-            Velocity(Vec2::new(0.0, 0.0)),
-            Transform::from_xyz(0.0, 0.0, 10.0),
+            commands,
+            planet,
+            Velocity(Vec2::from(body.velocity)),
+            Transform::from_xyz(body.position[0], body.position[1], layers::PLANETS),
         );
+    }
+}
 
-        for _ in 0..settings.n_objects {
-            let planet_radius = rng.gen::<f32>()
-                * (settings.max_planet_size - settings.min_planet_size)
-                + settings.min_planet_size;
-            let density: f32 = rng.gen::<f32>()
-                * (settings.max_planet_density - settings.min_planet_density)
-                + settings.min_planet_density;
-            let planet = Planet {
-                radius: planet_radius,
-                density,
-                color: Color::WHITE,
+fn spawn_many_orbits(
+    commands: &mut Commands,
+    settings: &Settings,
+    sun: &Planet,
+    n_objects: usize,
+    rng: &mut impl Rng,
+) {
+    let min_mass =
+        physics::radius_to_volume(settings.min_planet_size) * settings.min_planet_density;
+    let max_mass =
+        physics::radius_to_volume(settings.max_planet_size) * settings.max_planet_density;
+    // a separate generator so the colors don't change where the planets are
+    let mut color_rng = StdRng::seed_from_u64(settings.seed.wrapping_add(1));
+    for i in 0..n_objects {
+        let size_sample = rng.gen::<f32>();
+        let density: f32 = rng.gen::<f32>()
+            * (settings.max_planet_density - settings.min_planet_density)
+            + settings.min_planet_density;
+        let planet_radius = match settings.mass_distribution {
+            MassDistribution::Uniform => {
+                size_sample * (settings.max_planet_size - settings.min_planet_size)
+                    + settings.min_planet_size
+            }
+            MassDistribution::PowerLaw => {
+                let mass = physics::sample_power_law(
+                    size_sample,
+                    min_mass,
+                    max_mass,
+                    settings.power_law_slope,
+                );
+                physics::volume_to_radius(mass / density)
+            }
+        };
+        let planet = Planet {
+            radius: planet_radius,
+            density,
+            color: palette_color(settings.palette, i, n_objects, &mut color_rng),
+            is_sun: false,
+        };
+        let orbit_radius: f32 = rng.gen::<f32>()
+            * (settings.max_planet_orbit_radius - settings.min_planet_orbit_radius)
+            + settings.min_planet_orbit_radius;
+        let radian: f32 = rng.gen::<f32>() * 2.0 * PI;
+        let eccentricity: f32 = rng.gen::<f32>() * settings.max_eccentricity;
+        let true_anomaly: f32 = rng.gen::<f32>() * 2.0 * PI;
+        let mut orbital_velocity = physics::orbital_velocity(
+            settings.g * sun.mass(),
+            orbit_radius,
+            eccentricity,
+            true_anomaly,
+        );
+        // only draw when needed so the scenes of existing seeds don't change
+        if settings.retrograde_fraction > 0.0 && rng.gen::<f32>() < settings.retrograde_fraction {
+            orbital_velocity.y = -orbital_velocity.y;
+        }
+        if settings.velocity_jitter > 0.0 {
+            let angle = rng.gen::<f32>() * 2.0 * PI;
+            let magnitude = rng.gen::<f32>() * settings.velocity_jitter * orbital_velocity.length();
+            orbital_velocity += Vec2::new(angle.cos(), angle.sin()) * magnitude;
+        }
+        spawn_on_orbit(
+            commands,
+            settings,
+            planet,
+            (orbit_radius, radian),
+            orbital_velocity,
+            rng,
+        );
+    }
+}
+
+/// Thin ring of small particles with two larger shepherd moons embedded in it.
+fn spawn_ring(commands: &mut Commands, settings: &Settings, sun: &Planet, rng: &mut impl Rng) {
+    let mu = settings.g * sun.mass();
+    let ring_width = settings.ring_outer_radius - settings.ring_inner_radius;
+
+    for fraction in [0.35, 0.7] {
+        let shepherd = Planet {
+            radius: SHEPHERD_RADIUS,
+            density: settings.max_planet_density,
+            color: Color::WHITE,
+            is_sun: false,
+        };
+        let orbit_radius = settings.ring_inner_radius + fraction * ring_width;
+        let radian: f32 = rng.gen::<f32>() * 2.0 * PI;
+        let orbital_velocity = physics::orbital_velocity(mu, orbit_radius, 0.0, 0.0);
+        spawn_on_orbit(
+            commands,
+            settings,
+            shepherd,
+            (orbit_radius, radian),
+            orbital_velocity,
+            rng,
+        );
+    }
+
+    spawn_annulus(
+        commands,
+        settings,
+        mu,
+        (settings.ring_inner_radius, settings.ring_outer_radius),
+        settings.ring_particles,
+        RING_VELOCITY_DISPERSION,
+        rng,
+    );
+}
+
+/// Massive planet on a circular orbit and rings of particles on the orbits whose periods
+/// are in the enabled `RESONANCES` with it, from Kepler's third law.
+fn spawn_resonances(
+    commands: &mut Commands,
+    settings: &Settings,
+    sun: &Planet,
+    rng: &mut impl Rng,
+) {
+    let mu = settings.g * sun.mass();
+    let planet = Planet {
+        radius: RESONANT_PLANET_RADIUS,
+        density: settings.max_planet_density,
+        color: Color::ORANGE,
+        is_sun: false,
+    };
+    let planet_orbit = settings.resonant_planet_orbit;
+    spawn_on_orbit(
+        commands,
+        settings,
+        planet,
+        (planet_orbit, 0.0),
+        physics::orbital_velocity(mu, planet_orbit, 0.0, 0.0),
+        rng,
+    );
+    let planet_period = physics::kepler_period(mu, planet_orbit);
+
+    let enabled = RESONANCES
+        .iter()
+        .zip(settings.resonances)
+        .filter(|(_, enabled)| *enabled);
+    for (i, ((p, q), _)) in enabled.enumerate() {
+        let period = planet_period * *q as f32 / *p as f32;
+        let orbit_radius = physics::kepler_semi_major_axis(mu, period);
+        // one hue per ring so the resonances can be told apart
+        let color = Color::hsl(i as f32 * 60.0, 0.7, 0.7);
+        for _ in 0..settings.resonance_particles {
+            let particle = Planet {
+                radius: settings.min_planet_size,
+                density: settings.min_planet_density,
+                color,
                 is_sun: false,
             };
-            let orbit_radius: f32 = rng.gen::<f32>()
-                * (settings.max_planet_orbit_radius - settings.min_planet_orbit_radius)
-                + settings.min_planet_orbit_radius;
             let radian: f32 = rng.gen::<f32>() * 2.0 * PI;
-            let x: f32 = orbit_radius * radian.cos();
-            let y: f32 = orbit_radius * radian.sin();
-            let orbital_velocity = (settings.g * sun.mass() / orbit_radius).sqrt();
-            let vx: f32 = -orbital_velocity * radian.sin();
-            let vy: f32 = orbital_velocity * radian.cos();
-            spawn_planet(
-                &mut commands,
-                planet,
-                Velocity(Vec2::new(vx, vy)),
-                Transform::from_xyz(x, y, 10.0),
+            spawn_on_orbit(
+                commands,
+                settings,
+                particle,
+                (orbit_radius, radian),
+                physics::orbital_velocity(mu, orbit_radius, 0.0, 0.0),
+                rng,
             );
         }
     }
 }
 
-fn spawn_planet(commands: &mut Commands, planet: Planet, velocity: Velocity, transform: Transform) {
+/// Fills the area between the `radii` with small particles on near circular orbits,
+/// `dispersion` is the random part of the velocity as a fraction of the orbital speed.
+fn spawn_annulus(
+    commands: &mut Commands,
+    settings: &Settings,
+    mu: f32,
+    radii: (f32, f32),
+    n_particles: usize,
+    dispersion: f32,
+    rng: &mut impl Rng,
+) {
+    let (inner_radius, outer_radius) = radii;
+    for _ in 0..n_particles {
+        let particle = Planet {
+            radius: settings.min_planet_size,
+            density: settings.min_planet_density,
+            color: Color::WHITE,
+            is_sun: false,
+        };
+        let orbit_radius = rng.gen::<f32>() * (outer_radius - inner_radius) + inner_radius;
+        let radian: f32 = rng.gen::<f32>() * 2.0 * PI;
+        let orbital_velocity = physics::orbital_velocity(mu, orbit_radius, 0.0, 0.0);
+        let random_velocity = Vec2::new(rng.gen::<f32>() - 0.5, rng.gen::<f32>() - 0.5)
+            * dispersion
+            * orbital_velocity.length();
+        spawn_on_orbit(
+            commands,
+            settings,
+            particle,
+            (orbit_radius, radian),
+            orbital_velocity + random_velocity,
+            rng,
+        );
+    }
+}
+
+/// Spawns a planet at polar coordinates `(orbit_radius, radian)` around the origin,
+/// `velocity` is given as (radial, tangential) components.
+fn spawn_on_orbit(
+    commands: &mut Commands,
+    settings: &Settings,
+    planet: Planet,
+    (orbit_radius, radian): (f32, f32),
+    velocity: Vec2,
+    rng: &mut impl Rng,
+) {
+    let x: f32 = orbit_radius * radian.cos();
+    let y: f32 = orbit_radius * radian.sin();
+    // rotate from the (radial, tangential) frame into world coordinates
+    let vx: f32 = velocity.x * radian.cos() - velocity.y * radian.sin();
+    let vy: f32 = velocity.x * radian.sin() + velocity.y * radian.cos();
+    let mut transform = Transform::from_xyz(x, y, layers::PLANETS);
+    // the depth is only drawn when scattering so seeded layouts stay the same
+    if settings.depth_scatter > 0.0 {
+        // purely cosmetic, the physics only reads x and y and closer planets look bigger
+        let depth = rng.gen::<f32>() * 2.0 - 1.0;
+        transform.translation.z += depth * layers::PLANET_DEPTH;
+        transform.scale = Vec3::splat(1.0 + depth * settings.depth_scatter);
+    }
+    spawn_planet(commands, planet, Velocity(Vec2::new(vx, vy)), transform);
+}
+
+fn spawn_planet(
+    commands: &mut Commands,
+    planet: Planet,
+    velocity: Velocity,
+    transform: Transform,
+) -> Entity {
     let shape = shapes::Circle {
         radius: planet.radius,
         center: Default::default(),
@@ -312,137 +3325,1373 @@ fn spawn_planet(commands: &mut Commands, planet: Planet, velocity: Velocity, tra
         },
         transform,
     ));
-    entity_commands.insert(planet).insert(velocity);
+    entity_commands.insert(planet).insert(velocity).id()
+}
+
+/// Outline of a planet with the given radius centered at the origin.
+fn planet_path(shape: BodyShape, radius: f32) -> Path {
+    match shape {
+        BodyShape::Circle => ShapePath::build_as(&shapes::Circle {
+            radius,
+            center: Default::default(),
+        }),
+        BodyShape::Square => ShapePath::build_as(&shapes::Rectangle {
+            extents: Vec2::splat(2.0 * radius),
+            origin: RectangleOrigin::Center,
+        }),
+        BodyShape::Star => {
+            // five points alternating between the full and half radius
+            let points = (0..10)
+                .map(|i| {
+                    let angle = PI / 2.0 + i as f32 * PI / 5.0;
+                    let r = if i % 2 == 0 { radius } else { radius / 2.0 };
+                    Vec2::new(angle.cos(), angle.sin()) * r
+                })
+                .collect();
+            ShapePath::build_as(&shapes::Polygon {
+                points,
+                closed: true,
+            })
+        }
+    }
+}
+
+/// Planets are spawned as circles, this gives new planets the configured shape
+/// and rebuilds all of them when the shape is changed.
+fn apply_body_shape(
+    settings: Res<Settings>,
+    mut planets: Query<(&Planet, &mut Path)>,
+    added: Query<Entity, Added<Planet>>,
+    mut last_shape: Local<Option<BodyShape>>,
+) {
+    if *last_shape != Some(settings.body_shape) {
+        *last_shape = Some(settings.body_shape);
+        for (planet, mut path) in planets.iter_mut() {
+            *path = planet_path(settings.body_shape, planet.radius);
+        }
+    } else if settings.body_shape != BodyShape::Circle {
+        for entity in added.iter() {
+            if let Ok((planet, mut path)) = planets.get_mut(entity) {
+                *path = planet_path(settings.body_shape, planet.radius);
+            }
+        }
+    }
+}
+
+#[derive(Component)]
+struct ArenaWalls;
+
+/// Outline of the arena, rebuilt only when its size changes.
+fn draw_arena(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    walls: Query<Entity, With<ArenaWalls>>,
+    mut last_size: Local<Option<Vec2>>,
+) {
+    let size = settings
+        .arena
+        .then(|| Vec2::new(settings.arena_width, settings.arena_height));
+    if *last_size == size {
+        return;
+    }
+    *last_size = size;
+    for entity in walls.iter() {
+        commands.entity(entity).despawn();
+    }
+    if let Some(size) = size {
+        let shape = shapes::Rectangle {
+            extents: size,
+            origin: RectangleOrigin::Center,
+        };
+        commands
+            .spawn_bundle(GeometryBuilder::build_as(
+                &shape,
+                DrawMode::Stroke(StrokeMode::new(Color::rgb(0.5, 0.5, 0.5), 2.0)),
+                Transform::from_xyz(0.0, 0.0, layers::GRID),
+            ))
+            .insert(ArenaWalls);
+    }
+}
+
+fn spawn_flash(commands: &mut Commands, position: Vec2, max_radius: f32, now: f64) {
+    let shape = shapes::Circle {
+        radius: 1.0,
+        center: Default::default(),
+    };
+    commands
+        .spawn_bundle(GeometryBuilder::build_as(
+            &shape,
+            DrawMode::Stroke(StrokeMode::new(Color::ORANGE, 0.1)),
+            Transform::from_xyz(position.x, position.y, layers::MARKERS),
+        ))
+        .insert(Flash {
+            spawned_at: now,
+            max_radius,
+        });
 }
 
-fn spawn_trace(commands: &mut Commands, transform: Transform, live_until: f64) {
+fn trace_layer(stats: &Stats) -> f32 {
+    if stats.traces_over_planets {
+        layers::TRACES_OVER_PLANETS
+    } else {
+        layers::TRACES
+    }
+}
+
+/// Trace point living `lifetime` seconds from `now`. Glowing points have no sprite, they
+/// are drawn by the `GlowPlugin`.
+fn spawn_trace(
+    commands: &mut Commands,
+    transform: Transform,
+    (now, lifetime): (f64, f64),
+    color: Color,
+    glow: bool,
+) {
+    if glow {
+        commands.spawn_bundle((
+            transform,
+            Glow {
+                color,
+                half_size: GLOW_SIZE / 2.0,
+            },
+            Trace {
+                live_until: now + lifetime,
+                lifetime,
+                opacity: GLOW_OPACITY,
+            },
+        ));
+        return;
+    }
     commands
         .spawn_bundle(SpriteBundle {
             sprite: Sprite {
-                color: Color::GRAY,
+                color,
                 custom_size: Some(Vec2::new(1.0, 1.0)),
                 ..Default::default()
             },
             transform,
             ..Default::default()
         })
-        .insert(Trace { live_until });
+        .insert(Trace {
+            live_until: now + lifetime,
+            lifetime,
+            opacity: 1.0,
+        });
+}
+
+fn integrator_combo_box(ui: &mut egui::Ui, label: &str, integrator: &mut Integrator) {
+    egui::ComboBox::from_label(label)
+        .selected_text(format!("{:?}", integrator))
+        .show_ui(ui, |ui| {
+            for option in [
+                Integrator::ExplicitEuler,
+                Integrator::SemiImplicitEuler,
+                Integrator::Leapfrog,
+            ] {
+                ui.selectable_value(integrator, option, format!("{:?}", option));
+            }
+        });
 }
 
 fn ui_box(
     mut ev_clear_traces: EventWriter<ClearTraces>,
     mut ev_reset: EventWriter<Reset>,
+    mut ev_launch: EventWriter<Launch>,
+    (mut ev_predict, mut ev_stir, mut ev_copy_scene, mut ev_paste_scene): (
+        EventWriter<Predict>,
+        EventWriter<Stir>,
+        EventWriter<CopyScene>,
+        EventWriter<PasteScene>,
+    ),
+    mut ev_change_count: EventWriter<ChangePlanetCount>,
+    mut ev_screenshot: EventWriter<Screenshot>,
     mut settings: ResMut<Settings>,
     diagnostics: Res<Diagnostics>,
     mut egui_context: ResMut<EguiContext>,
     mut stats: ResMut<Stats>,
+    mut clear_color: ResMut<ClearColor>,
+    mut pancam: Query<&mut PanCam>,
+    mut replay: ResMut<Replay>,
+    merge_log: Res<MergeLog>,
+    selected: Query<(&Planet, Option<&BodyId>, Option<&Lineage>), With<Selected>>,
     time: Res<Time>,
 ) {
-    egui::Window::new("Moon creator").show(egui_context.ctx_mut(), |ui| {
-        if let Some(fps) = diagnostics.get(FrameTimeDiagnosticsPlugin::FPS) {
-            if let Some(average) = fps.average() {
-                // Update the value of the second section
-                ui.label("WASD to move, drag to move,\nscrool wheel to zoom in/out");
-                ui.label(format!("Time {:.2}", time.seconds_since_startup()));
+    let ctx = egui_context.ctx_mut();
+    egui::TopBottomPanel::top("stats").show(ctx, |ui| {
+        ui.horizontal_wrapped(|ui| {
+            let toggle = if stats.hide_settings_panel {
+                "Show settings"
+            } else {
+                "Hide settings"
+            };
+            if ui.button(toggle).clicked() {
+                stats.hide_settings_panel = !stats.hide_settings_panel;
+            }
+            let pause = if stats.paused { "Resume" } else { "Pause" };
+            if ui.button(pause).clicked() {
+                stats.paused = !stats.paused;
+            }
+            if stats.paused && ui.button("Step").clicked() {
+                stats.step_pending = true;
+            }
+            if let (true, Some(collision)) = (stats.paused, stats.paused_collision) {
+                let id = |id: Option<BodyId>| id.map_or("?".to_string(), |id| format!("#{}", id.0));
+                ui.label(format!(
+                    "Paused before {} and {} merge",
+                    id(collision.first),
+                    id(collision.second)
+                ));
+            }
+            if let Some(average) = diagnostics
+                .get(FrameTimeDiagnosticsPlugin::FPS)
+                .and_then(|fps| fps.average())
+            {
                 ui.label(format!("FPS {:.2}", average));
-                ui.label(format!("Number of objects {:}", stats.n_objects));
-                ui.checkbox(&mut stats.center_on_largest, "Center on the largest");
-                ui.checkbox(&mut stats.draw_traces, "Draw traces");
-                ui.add(egui::Slider::new(&mut settings.g, 0.5..=100.0).text("G constant"));
-                ui.add(egui::Slider::new(&mut settings.time_step, 1.0..=1000.0).text("Time step"));
-                ui.label("Higher value means slower, but more precise simulation");
-                ui.checkbox(&mut settings.collisions, "Enable colissions");
-                if ui.button("Clear traces").clicked() {
-                    ev_clear_traces.send(ClearTraces);
-                };
-                ui.label("Simulation settings (need restart)");
+            }
+            if let Some(count) = diagnostics
+                .get(EntityCountDiagnosticsPlugin::ENTITY_COUNT)
+                .and_then(|count| count.value())
+            {
+                ui.label(format!("Entities {}", count));
+            }
+            let number = |value: f64| format_number(value, stats.number_format);
+            let (time_scale, unit) = time_unit(&settings, stats.scenario);
+            ui.label(format!(
+                "Simulated time {} {}",
+                number(stats.sim_time * time_scale as f64),
+                unit
+            ));
+            ui.label(format!("Wall clock {:.2} s", time.seconds_since_startup()));
+            ui.label(format!(
+                "Number of objects {:} / {:}",
+                stats.n_objects, settings.max_bodies
+            ));
+            ui.label(format!("Ejected objects {:}", stats.ejected_count));
+            ui.label(format!("Collisions {:}", stats.collision_count));
+            ui.label(format!("Total mass {}", number(stats.total_mass as f64)));
+            ui.label(format!(
+                "Bound / unbound {} / {}",
+                stats.bound_count, stats.unbound_count
+            ));
+            ui.label(format!(
+                "Accretion {} merges, {} planets lost per time unit",
+                number(stats.accretion_rate as f64),
+                number(stats.body_decline_rate as f64)
+            ));
+            if let Some((entity_1, entity_2, distance)) = stats.closest_pair {
+                ui.label(format!(
+                    "Closest pair {} and {} at {}",
+                    entity_1.id(),
+                    entity_2.id(),
+                    number(distance as f64)
+                ));
+            }
+            ui.label(format!(
+                "Angular momentum {}",
+                number(stats.angular_momentum as f64)
+            ));
+            if let Some(initial) = stats.initial_angular_momentum {
+                if initial != 0.0 {
+                    ui.label(format!(
+                        "drift {:.3}%",
+                        (stats.angular_momentum - initial) / initial.abs() * 100.0
+                    ));
+                }
+            }
+            ui.label(format!("Non-finite states {:}", stats.nan_events));
+            if let Some(cursor) = stats.cursor_position {
+                ui.label(format!(
+                    "Cursor ({}, {}), {} from the barycenter",
+                    number(cursor.x as f64),
+                    number(cursor.y as f64),
+                    number(cursor.distance(stats.barycenter) as f64)
+                ));
+            }
+        });
+    });
+
+    if stats.hide_settings_panel {
+        return;
+    }
+    let floating = stats.floating_settings_panel;
+    let pinned = stats.pin_settings_panel;
+    let window_pos = stats
+        .settings_window_pos
+        .unwrap_or_else(|| egui::pos2(10.0, 60.0));
+    let opacity = 1.0 - stats.settings_panel_transparency;
+    let contents = |ui: &mut egui::Ui| {
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            ui.label("WASD to move, drag to move,\nscrool wheel to zoom in/out");
+            ui.checkbox(
+                &mut stats.floating_settings_panel,
+                "Movable settings window instead of a side panel",
+            );
+            if stats.floating_settings_panel {
+                ui.checkbox(&mut stats.pin_settings_panel, "Pin the settings window on top");
+            }
+            ui.add(
+                egui::Slider::new(&mut stats.settings_panel_transparency, 0.0..=0.9)
+                    .text("Settings transparency"),
+            );
+            if stats.scenario == Some(Scenario::Ephemeris) {
+                ui.label("The ephemeris scenario counts the time in years");
+            } else {
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut settings.sim_time_scale)
+                            .speed(0.01)
+                            .prefix("Time scale "),
+                    );
+                    ui.text_edit_singleline(&mut settings.sim_time_unit);
+                });
+            }
+            ui.collapsing("Accretion rate", |ui| {
+                let values = stats
+                    .accretion_history
+                    .iter()
+                    .enumerate()
+                    .map(|(i, rate)| egui::plot::Value::new(i as f64, *rate as f64));
+                egui::plot::Plot::new("accretion_rate")
+                    .height(100.0)
+                    .show(ui, |plot_ui| {
+                        plot_ui.line(egui::plot::Line::new(egui::plot::Values::from_values_iter(
+                            values,
+                        )))
+                    });
+            });
+            ui.checkbox(&mut stats.center_on_largest, "Center on the largest");
+            ui.horizontal(|ui| {
+                if ui.button("Zoom to selected").clicked() {
+                    stats.focus_selected_pending = true;
+                }
+                ui.checkbox(&mut stats.follow_selected, "Follow the selected planet");
+            });
+            for mut pancam in pancam.iter_mut() {
+                ui.checkbox(&mut pancam.inertia, "Camera keeps moving after dragging");
+            }
+            ui.add(
+                egui::Slider::new(&mut settings.min_zoom, 0.00001..=1.0)
+                    .logarithmic(true)
+                    .text("Closest zoom (units per pixel)"),
+            );
+            ui.add(
+                egui::Slider::new(&mut settings.max_zoom, 1.0..=1000.0)
+                    .logarithmic(true)
+                    .text("Farthest zoom (units per pixel)"),
+            );
+            egui::ComboBox::from_label("Traces")
+                .selected_text(format!("{:?}", stats.trace_mode))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut stats.trace_mode, TraceMode::None, "None");
+                    ui.selectable_value(&mut stats.trace_mode, TraceMode::All, "All");
+                    ui.selectable_value(
+                        &mut stats.trace_mode,
+                        TraceMode::Selected,
+                        "Selected (click a planet)",
+                    );
+                    ui.selectable_value(&mut stats.trace_mode, TraceMode::LargestN, "LargestN");
+                });
+            if stats.trace_mode == TraceMode::LargestN {
+                ui.add(
+                    egui::Slider::new(&mut stats.trace_largest_n, 1..=50)
+                        .text("Number of traced planets"),
+                );
+            }
+            ui.add(
+                egui::Slider::new(&mut stats.trace_spacing, 0.0..=100.0)
+                    .text("Trace spacing (0 samples every few frames)"),
+            );
+            ui.checkbox(&mut stats.gray_traces, "Gray traces");
+            ui.checkbox(&mut stats.glow_traces, "Glowing traces");
+            ui.checkbox(&mut settings.speed_trails, "Longer traces behind faster planets");
+            if settings.speed_trails {
+                ui.add(
+                    egui::Slider::new(&mut settings.speed_trail_sensitivity, 0.001..=1.0)
+                        .logarithmic(true)
+                        .text("Trace length per unit of speed"),
+                );
+            }
+            ui.checkbox(&mut stats.traces_over_planets, "Traces over the planets");
+            ui.checkbox(&mut stats.tint_unbound, "Escaping planets in red");
+            ui.checkbox(&mut stats.escape_arrows, "Arrows on escaping planets");
+            ui.horizontal(|ui| {
+                ui.label("Color planets by");
+                ui.radio_value(&mut stats.color_mode, ColorMode::Original, "original color");
+                ui.radio_value(&mut stats.color_mode, ColorMode::MergeCount, "merge count");
+                ui.radio_value(&mut stats.color_mode, ColorMode::Temperature, "temperature");
+            });
+            if stats.color_mode == ColorMode::Temperature {
+                ui.add(
+                    egui::Slider::new(&mut settings.cooling_rate, 0.0..=5.0).text("Cooling rate"),
+                );
+            }
+            ui.horizontal(|ui| {
+                ui.label("Numbers");
+                ui.radio_value(&mut stats.number_format, NumberFormat::Fixed, "fixed");
+                ui.radio_value(
+                    &mut stats.number_format,
+                    NumberFormat::Scientific,
+                    "scientific",
+                );
+                ui.radio_value(&mut stats.number_format, NumberFormat::SiSuffix, "k, M, G");
+            });
+            egui::ComboBox::from_label("Trace fade")
+                .selected_text(format!("{:?}", stats.trace_fade))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut stats.trace_fade, TraceFade::Off, "Off");
+                    ui.selectable_value(&mut stats.trace_fade, TraceFade::Linear, "Linear");
+                    ui.selectable_value(
+                        &mut stats.trace_fade,
+                        TraceFade::Exponential,
+                        "Exponential",
+                    );
+                });
+            ui.horizontal(|ui| {
+                ui.label("Draw planets as");
+                ui.radio_value(&mut settings.render_mode, RenderMode::Shapes, "circles");
+                ui.radio_value(
+                    &mut settings.render_mode,
+                    RenderMode::Points,
+                    "points (faster)",
+                );
+            });
+            if settings.render_mode == RenderMode::Shapes {
+                ui.horizontal(|ui| {
+                    ui.label("Shape");
+                    ui.radio_value(&mut settings.body_shape, BodyShape::Circle, "circle");
+                    ui.radio_value(&mut settings.body_shape, BodyShape::Square, "square");
+                    ui.radio_value(&mut settings.body_shape, BodyShape::Star, "star");
+                });
+            }
+            ui.add(
+                egui::Slider::new(&mut settings.perspective_tilt, 0.0..=80.0)
+                    .text("Perspective tilt (degrees)"),
+            );
+            ui.checkbox(
+                &mut settings.lensing,
+                "Lensing rings around the largest body",
+            );
+            if settings.lensing {
+                ui.add(
+                    egui::Slider::new(&mut settings.lensing_strength, 0.0..=0.5)
+                        .text("Lensing strength"),
+                );
+            }
+            ui.checkbox(&mut stats.merge_flash, "Flash on merge");
+            ui.checkbox(&mut settings.animate_merges, "Absorbed planets shrink into the merged one");
+            ui.checkbox(&mut stats.draw_grid, "Draw grid");
+            ui.checkbox(&mut stats.draw_stars, "Draw stars");
+            ui.checkbox(&mut stats.draw_minimap, "Draw minimap");
+            ui.checkbox(
+                &mut stats.draw_crosshairs,
+                "Mark the barycenter and the suns",
+            );
+            ui.checkbox(
+                &mut stats.draw_hill_sphere,
+                "Hill sphere of the selected planet",
+            );
+            ui.checkbox(
+                &mut settings.acceleration_outlines,
+                "Outline width shows the acceleration",
+            );
+            if settings.acceleration_outlines {
+                ui.add(
+                    egui::Slider::new(&mut settings.acceleration_outline_scale, 0.01..=100.0)
+                        .logarithmic(true)
+                        .text("Outline scale"),
+                );
+            }
+            ui.checkbox(&mut stats.show_labels, "Show planet names");
+            ui.checkbox(
+                &mut stats.draw_momentum,
+                "Arrow along the total momentum",
+            );
+            if stats.draw_momentum {
+                ui.add(
+                    egui::Slider::new(&mut settings.momentum_arrow_scale, 0.000001..=10.0)
+                        .logarithmic(true)
+                        .text("Arrow scale"),
+                );
+            }
+            ui.horizontal(|ui| {
+                let mut background = [clear_color.0.r(), clear_color.0.g(), clear_color.0.b()];
+                if ui.color_edit_button_rgb(&mut background).changed() {
+                    clear_color.0 = Color::rgb(background[0], background[1], background[2]);
+                }
+                ui.label("Background color");
+            });
+            ui.add(
+                egui::Slider::new(&mut settings.g, -100.0..=100.0)
+                    .text("G constant (negative repels, planets start at rest)"),
+            );
+            ui.checkbox(
+                &mut settings.rescale_on_g_change,
+                "Rescale velocities when G changes",
+            );
+            ui.add(
+                egui::Slider::new(&mut settings.gr_correction, 0.0..=10000.0)
+                    .text("Relativistic precession"),
+            );
+            ui.add(
+                egui::Slider::new(&mut settings.gas_drag, 0.0..=1.0)
+                    .text("Gas drag (orbits decay and circularize)"),
+            );
+            ui.horizontal(|ui| {
+                ui.label("Softening");
+                ui.radio_value(
+                    &mut settings.softening_mode,
+                    SofteningMode::Constant,
+                    "constant",
+                );
+                ui.radio_value(
+                    &mut settings.softening_mode,
+                    SofteningMode::RadiusScaled,
+                    "scaled by radii",
+                );
+            });
+            let softening_range = match settings.softening_mode {
+                SofteningMode::Constant => 0.0..=50.0,
+                SofteningMode::RadiusScaled => 0.0..=2.0,
+            };
+            ui.add(
+                egui::Slider::new(&mut settings.softening, softening_range)
+                    .text("Softening length / factor"),
+            );
+            ui.checkbox(
+                &mut settings.test_particle_mode,
+                "Light planets don't attract each other (faster)",
+            );
+            if settings.test_particle_mode {
+                ui.add(
+                    egui::Slider::new(&mut settings.test_particle_mass, 1.0..=1000000.0)
+                        .logarithmic(true)
+                        .text("Mass of attracting bodies"),
+                );
+            }
+            ui.add(
+                egui::Slider::new(&mut settings.time_step, MIN_TIME_STEP..=MAX_TIME_STEP)
+                    .text("Time step ([ and ] keys)"),
+            );
+            ui.label("Higher value means slower, but more precise simulation");
+            ui.add(egui::Slider::new(&mut settings.substeps, 1..=16).text("Substeps per frame"));
+            ui.label("More substeps keep the speed and improve close encounters, but cost more per frame");
+            ui.add(
+                egui::Slider::new(&mut settings.physics_every_n_frames, 1..=8)
+                    .text("Simulate every n-th frame"),
+            );
+            ui.horizontal(|ui| {
+                ui.checkbox(
+                    &mut settings.energy_correction,
+                    "Keep the energy constant (not physical)",
+                );
+                if settings.energy_correction {
+                    ui.add(
+                        egui::Slider::new(&mut settings.energy_correction_interval, 1..=1000)
+                            .text("every n steps"),
+                    );
+                }
+            });
+            if settings.energy_correction && settings.gas_drag > 0.0 {
+                ui.label("The energy isn't corrected while the gas drag is on");
+            }
+            ui.checkbox(
+                &mut settings.slow_motion,
+                "Slow motion when planets get close",
+            );
+            if settings.slow_motion {
+                ui.add(
+                    egui::Slider::new(&mut settings.slow_motion_distance, 1.0..=500.0)
+                        .logarithmic(true)
+                        .text("Slow down within this distance"),
+                );
+                ui.add(
+                    egui::Slider::new(&mut settings.slow_motion_factor, 0.01..=1.0)
+                        .logarithmic(true)
+                        .text("Slowest pace"),
+                );
+            }
+            ui.checkbox(&mut settings.collisions, "Enable colissions");
+            if let Some(scenario) = stats.scenario.filter(|scenario| !scenario.allows_collisions()) {
+                ui.label(format!("The {:?} scenario runs without collisions", scenario));
+            }
+            if settings.collisions {
+                ui.horizontal(|ui| {
+                    ui.checkbox(
+                        &mut settings.pause_on_collision,
+                        "Pause before the first merge",
+                    );
+                    ui.checkbox(&mut settings.center_on_collision, "and center on it");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Merge");
+                    ui.checkbox(
+                        &mut settings.merge_policy.planets_with_planets,
+                        "planets with planets",
+                    );
+                    ui.checkbox(
+                        &mut settings.merge_policy.planets_with_suns,
+                        "planets with suns",
+                    );
+                    ui.checkbox(&mut settings.merge_policy.suns_with_suns, "suns with suns");
+                });
+            }
+            ui.checkbox(
+                &mut settings.resolve_overlaps,
+                "Push apart overlapping planets which don't merge",
+            );
+            if settings.resolve_overlaps {
+                ui.add(
+                    egui::Slider::new(&mut settings.overlap_push, 0.05..=1.0)
+                        .text("Part of the overlap removed per step"),
+                );
+            }
+            ui.add(
+                egui::Slider::new(&mut settings.merge_overlap, 0.1..=1.0)
+                    .text("Merge when centers are within this part of the radii"),
+            );
+            ui.checkbox(
+                &mut settings.collision_grid,
+                "Find collisions on a grid (faster with many planets)",
+            );
+            ui.horizontal(|ui| {
+                ui.label("Merge by");
+                ui.radio_value(
+                    &mut settings.merge_dimension,
+                    MergeDimension::Volume,
+                    "volume (3D)",
+                );
+                ui.radio_value(
+                    &mut settings.merge_dimension,
+                    MergeDimension::Area,
+                    "area (2D)",
+                );
+            });
+            ui.label("Merged planets grow slower by volume, like spheres, than by area, like disks");
+            ui.checkbox(&mut settings.impact_debris, "Fast impacts eject debris");
+            if settings.impact_debris {
+                ui.add(
+                    egui::Slider::new(&mut settings.debris_speed_threshold, 0.0..=50.0)
+                        .text("Minimum impact speed"),
+                );
+                ui.add(
+                    egui::Slider::new(&mut settings.debris_count, 2..=20).text("Debris fragments"),
+                );
+            }
+            ui.checkbox(&mut settings.arena, "Bounce off the walls of an arena");
+            if settings.arena {
+                ui.add(
+                    egui::Slider::new(&mut settings.arena_width, 100.0..=10000.0)
+                        .text("Arena width"),
+                );
+                ui.add(
+                    egui::Slider::new(&mut settings.arena_height, 100.0..=10000.0)
+                        .text("Arena height"),
+                );
+            }
+            ui.checkbox(&mut settings.enable_tidal_disruption, "Tidal disruption");
+            if settings.enable_tidal_disruption {
+                ui.add(
+                    egui::Slider::new(&mut settings.roche_coefficient, 0.5..=5.0)
+                        .text("Roche limit coefficient"),
+                );
+            }
+            ui.horizontal(|ui| {
+                ui.label("On NaN");
+                ui.radio_value(
+                    &mut settings.non_finite_policy,
+                    NonFinitePolicy::Despawn,
+                    "remove planet",
+                );
+                ui.radio_value(
+                    &mut settings.non_finite_policy,
+                    NonFinitePolicy::Stop,
+                    "stop planet",
+                );
+            });
+            ui.add(
+                egui::Slider::new(&mut settings.max_bodies, 10..=5000)
+                    .text("Maximum number of objects"),
+            );
+            ui.checkbox(
+                &mut settings.auto_scale_bodies,
+                "Remove small objects when FPS is low",
+            );
+            if settings.auto_scale_bodies {
+                ui.add(
+                    egui::Slider::new(&mut settings.auto_scale_fps, 10.0..=120.0)
+                        .text("Target FPS"),
+                );
+                ui.add(
+                    egui::Slider::new(&mut settings.auto_scale_min_bodies, 10..=1000)
+                        .text("Minimum number of objects"),
+                );
+                ui.checkbox(
+                    &mut settings.auto_scale_respawn,
+                    "Add objects back when FPS is high",
+                );
+                if settings.auto_scale_respawn {
+                    ui.add(
+                        egui::Slider::new(&mut settings.auto_scale_max_bodies, 10..=5000)
+                            .text("Maximum number of objects after adding"),
+                    );
+                }
+            }
+            ui.checkbox(&mut settings.cull_escaped, "Remove escaped objects");
+            ui.add(
+                egui::Slider::new(&mut settings.cull_distance, 1000.0..=20000.0)
+                    .text("Escape distance from barycenter"),
+            );
+            ui.checkbox(&mut settings.blend_colors_on_merge, "Blend colors on merge");
+            if ui.button("Clear traces").clicked() {
+                ev_clear_traces.send(ClearTraces);
+            };
+            ui.horizontal(|ui| {
+                if ui.button("Screenshot (F12)").clicked() {
+                    ev_screenshot.send(Screenshot);
+                }
+                ui.checkbox(&mut settings.screenshot_ui, "Include the UI");
+            });
+            ui.horizontal(|ui| {
+                let step = settings.planet_count_step;
+                if ui.button(format!("+{}", step)).clicked() {
+                    ev_change_count.send(ChangePlanetCount(step as i64));
+                }
+                if ui.button(format!("-{}", step)).clicked() {
+                    ev_change_count.send(ChangePlanetCount(-(step as i64)));
+                }
+                ui.add(egui::DragValue::new(&mut settings.planet_count_step).clamp_range(1..=1000));
+                ui.label("planets without restart");
+            });
+            ui.collapsing("Replay", |ui| {
+                ui.horizontal(|ui| {
+                    match replay.mode() {
+                        ReplayMode::Idle => {
+                            if ui.button("Record").clicked() {
+                                replay.record();
+                            }
+                            if ui.button("Play").clicked() {
+                                replay.play();
+                            }
+                        }
+                        ReplayMode::Recording | ReplayMode::Playing => {
+                            if ui.button("Stop").clicked() {
+                                replay.stop();
+                            }
+                        }
+                    }
+                    ui.checkbox(&mut replay.looping, "Loop");
+                });
+                ui.label(format!("{} frames recorded", replay.frame_count()));
+            });
+            ui.collapsing("Selected planet", |ui| match selected.iter().next() {
+                Some((planet, body_id, lineage)) => {
+                    if let Some(body_id) = body_id {
+                        ui.label(format!("Id #{}", body_id.0));
+                    }
+                    ui.horizontal(|ui| {
+                        ui.label("Name");
+                        ui.text_edit_singleline(&mut stats.selected_name);
+                    });
+                    ui.label(format!("Mass {:.2}", planet.mass()));
+                    ui.label(format!("Radius {:.2}", planet.radius));
+                    if let Some(lineage) = lineage {
+                        let (time_scale, unit) = time_unit(&settings, stats.scenario);
+                        let age = (stats.sim_time - lineage.spawn_time) * time_scale as f64;
+                        ui.label(format!("Age {:.2} {}", age, unit));
+                        ui.label(format!("Merges {}", lineage.merges));
+                    }
+                }
+                None => {
+                    ui.label("Click a planet to select it");
+                }
+            });
+            ui.collapsing("Binary", |ui| match stats.binary_orbit {
+                Some(binary) => {
+                    ui.label(format!("Separation {:.2}", binary.separation));
+                    ui.label(format!("Relative speed {:.2}", binary.relative_speed));
+                    if let Some(elements) = binary.elements {
+                        ui.label(format!("Semi-major axis {:.2}", elements.semi_major_axis));
+                        ui.label(format!("Eccentricity {:.3}", elements.eccentricity));
+                        let (time_scale, unit) = time_unit(&settings, stats.scenario);
+                        match elements.period {
+                            Some(period) => {
+                                ui.label(format!("Period {:.2} {}", period * time_scale, unit))
+                            }
+                            None => ui.label("Not bound"),
+                        };
+                    }
+                }
+                None => {
+                    ui.label("Shift+click another planet to pin it as the binary partner of the selected one");
+                }
+            });
+            ui.collapsing("Merge log", |ui| {
+                ui.label(format!("{} merges", merge_log.0.len()));
+                #[cfg(not(target_arch = "wasm32"))]
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut settings.log_merges, "Write CSV to file");
+                    ui.text_edit_singleline(&mut settings.merge_log_path);
+                });
+                #[cfg(target_arch = "wasm32")]
+                if ui.button("Download CSV").clicked() {
+                    if let Err(err) = merge_log::download(&merge_log) {
+                        warn!("Merge log download failed: {}", err);
+                    }
+                }
+                for record in merge_log.0.iter().rev().take(MERGE_LOG_SHOWN) {
+                    ui.label(format!(
+                        "#{} absorbed #{} at {:.2}",
+                        record.survivor.0, record.absorbed.0, record.sim_time
+                    ));
+                }
+            });
+            ui.collapsing("Anchors", |ui| {
+                ui.checkbox(&mut stats.place_anchors, "Click to place an anchor");
+                ui.add(
+                    egui::Slider::new(&mut settings.anchor_mass, 0.0..=1000000.0)
+                        .logarithmic(true)
+                        .text("Anchor mass"),
+                );
+                ui.label("Anchors attract planets but never move or merge");
+            });
+            ui.collapsing("Launch a planet", |ui| {
+                ui.add(
+                    egui::Slider::new(&mut settings.launch_semi_major_axis, 50.0..=2000.0)
+                        .text("Semi-major axis"),
+                );
+                ui.add(
+                    egui::Slider::new(&mut settings.launch_eccentricity, 0.0..=0.95)
+                        .text("Eccentricity"),
+                );
+                ui.add(
+                    egui::Slider::new(&mut settings.launch_periapsis_angle, 0.0..=360.0)
+                        .text("Periapsis direction (degrees)"),
+                );
+                ui.checkbox(&mut settings.launch_preview, "Preview the orbit");
+                if ui.button("Launch").clicked() {
+                    ev_launch.send(Launch);
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Stir").clicked() {
+                    ev_stir.send(Stir);
+                }
+                ui.add(
+                    egui::Slider::new(&mut settings.stir_strength, 0.0..=50.0).text("Largest kick"),
+                );
+            });
+            ui.add(
+                egui::Slider::new(&mut settings.nudge_speed, 0.01..=10.0)
+                    .logarithmic(true)
+                    .text("Arrow keys nudge the planet under the cursor by"),
+            );
+            ui.collapsing("Prediction", |ui| {
                 ui.add(
-                    egui::Slider::new(&mut settings.n_objects, 10..=1000).text("Number of planets"),
+                    egui::Slider::new(&mut settings.prediction_steps, 100..=20000)
+                        .logarithmic(true)
+                        .text("Steps ahead"),
+                );
+                ui.horizontal(|ui| {
+                    if ui.button("Predict").clicked() {
+                        ev_predict.send(Predict);
+                    }
+                    ui.label("\"Clear traces\" removes the paths");
+                });
+            });
+            #[cfg(not(target_arch = "wasm32"))]
+            ui.collapsing("Diagnostics log", |ui| {
+                ui.checkbox(&mut settings.log_diagnostics, "Write to file");
+                ui.text_edit_singleline(&mut settings.log_path);
+                ui.add(
+                    egui::Slider::new(&mut settings.log_interval, 1..=1000)
+                        .text("Steps between samples"),
+                );
+            });
+            #[cfg(not(target_arch = "wasm32"))]
+            ui.horizontal(|ui| {
+                ui.label("FPS limit");
+                for (fps, label) in [(30, "30"), (60, "60"), (120, "120"), (0, "uncapped")] {
+                    ui.radio_value(&mut settings.target_fps, fps, label);
+                }
+                ui.checkbox(&mut settings.vsync, "VSync");
+            });
+            ui.horizontal(|ui| {
+                ui.label("Anti-aliasing");
+                // WebGL only supports 1 or 4 samples
+                #[cfg(target_arch = "wasm32")]
+                let sample_counts = [1, 4];
+                #[cfg(not(target_arch = "wasm32"))]
+                let sample_counts = [1, 2, 4, 8];
+                for samples in sample_counts {
+                    let label = if samples == 1 {
+                        "off".to_string()
+                    } else {
+                        format!("{}x", samples)
+                    };
+                    ui.radio_value(&mut settings.msaa_samples, samples, label);
+                }
+            });
+            ui.label("Simulation settings (need restart)");
+            integrator_combo_box(ui, "Integrator", &mut settings.integrator);
+            ui.checkbox(
+                &mut settings.compare_integrators,
+                "Compare with a second integrator (red copy)",
+            );
+            if settings.compare_integrators {
+                integrator_combo_box(
+                    ui,
+                    "Comparison integrator",
+                    &mut settings.comparison_integrator,
+                );
+            }
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(&mut settings.seed).prefix("Seed "));
+                if ui.button("Random seed").clicked() {
+                    settings.seed = rand::random();
+                }
+            });
+            let previous_scenario = settings.scenario;
+            egui::ComboBox::from_label("Scenario")
+                .selected_text(format!("{:?}", settings.scenario))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut settings.scenario, Scenario::ManyOrbits, "ManyOrbits");
+                    ui.selectable_value(&mut settings.scenario, Scenario::Ring, "Ring");
+                    ui.selectable_value(&mut settings.scenario, Scenario::Belt, "Belt");
+                    ui.selectable_value(&mut settings.scenario, Scenario::Ephemeris, "Ephemeris");
+                    ui.selectable_value(&mut settings.scenario, Scenario::Resonances, "Resonances");
+                    #[cfg(not(target_arch = "wasm32"))]
+                    ui.selectable_value(&mut settings.scenario, Scenario::Scene, "Scene file");
+                });
+            if settings.scenario != previous_scenario && !settings.keep_settings_on_scenario_change
+            {
+                let scenario = settings.scenario;
+                scenario.apply_defaults(&mut settings);
+            }
+            ui.checkbox(
+                &mut settings.keep_settings_on_scenario_change,
+                "Keep my settings when switching scenarios",
+            );
+            ui.add(egui::Slider::new(&mut settings.n_objects, 10..=1000).text("Number of planets"));
+            ui.checkbox(&mut settings.collisions, "Enable colissions");
+            ui.add(
+                egui::Slider::new(&mut settings.min_planet_size, 0.5..=3.0)
+                    .text("Minimum planet radius"),
+            );
+            ui.add(
+                egui::Slider::new(&mut settings.max_planet_size, 3.0..=10.0)
+                    .text("Maximum planet radius"),
+            );
+            ui.add(
+                egui::Slider::new(&mut settings.min_planet_density, 0.5..=5.0)
+                    .text("Minimum planet density"),
+            );
+            ui.add(
+                egui::Slider::new(&mut settings.max_planet_density, 0.5..=50.0)
+                    .text("Maximum planet density"),
+            );
+            ui.add(
+                egui::Slider::new(&mut settings.min_planet_orbit_radius, 100.0..=500.0)
+                    .text("Minimum planet orbit radius"),
+            );
+            ui.add(
+                egui::Slider::new(&mut settings.max_planet_orbit_radius, 500.0..=2000.0)
+                    .text("Maximum planet orbit radius"),
+            );
+            ui.add(
+                egui::Slider::new(&mut settings.max_eccentricity, 0.0..=0.9)
+                    .text("Maximum orbit eccentricity"),
+            );
+            ui.horizontal(|ui| {
+                ui.label("Planet masses");
+                ui.radio_value(
+                    &mut settings.mass_distribution,
+                    MassDistribution::Uniform,
+                    "uniform",
+                );
+                ui.radio_value(
+                    &mut settings.mass_distribution,
+                    MassDistribution::PowerLaw,
+                    "power law",
+                );
+            });
+            if settings.mass_distribution == MassDistribution::PowerLaw {
+                ui.add(
+                    egui::Slider::new(&mut settings.power_law_slope, 0.0..=4.0)
+                        .text("Power law slope"),
+                );
+            }
+            ui.horizontal(|ui| {
+                ui.label("Planet colors");
+                for (palette, label) in [
+                    (Palette::White, "white"),
+                    (Palette::Rainbow, "rainbow"),
+                    (Palette::Viridis, "viridis"),
+                    (Palette::Random, "random"),
+                ] {
+                    ui.radio_value(&mut settings.palette, palette, label);
+                }
+            });
+            ui.add(
+                egui::Slider::new(&mut settings.retrograde_fraction, 0.0..=1.0)
+                    .text("Fraction of retrograde orbits"),
+            );
+            ui.add(
+                egui::Slider::new(&mut settings.velocity_jitter, 0.0..=0.5).text("Velocity jitter"),
+            );
+            ui.add(
+                egui::Slider::new(&mut settings.depth_scatter, 0.0..=0.5)
+                    .text("Depth scatter (cosmetic)"),
+            );
+            if settings.scenario == Scenario::Ring {
+                ui.add(
+                    egui::Slider::new(&mut settings.ring_particles, 10..=2000)
+                        .text("Ring particles"),
                 );
-                ui.checkbox(&mut settings.collisions, "Enable colissions");
                 ui.add(
-                    egui::Slider::new(&mut settings.min_planet_size, 0.5..=3.0)
-                        .text("Minimum planet radius"),
+                    egui::Slider::new(&mut settings.ring_inner_radius, 100.0..=1000.0)
+                        .text("Ring inner radius"),
                 );
                 ui.add(
-                    egui::Slider::new(&mut settings.max_planet_size, 3.0..=10.0)
-                        .text("Maximum planet radius"),
+                    egui::Slider::new(&mut settings.ring_outer_radius, 100.0..=2000.0)
+                        .text("Ring outer radius"),
+                );
+                ui.label("Collisions are disabled when the ring starts");
+            }
+            if settings.scenario == Scenario::Belt {
+                ui.add(
+                    egui::Slider::new(&mut settings.belt_particles, 10..=3000)
+                        .text("Belt particles"),
                 );
                 ui.add(
-                    egui::Slider::new(&mut settings.min_planet_density, 0.5..=5.0)
-                        .text("Minimum planet density"),
+                    egui::Slider::new(&mut settings.belt_inner_radius, 100.0..=1000.0)
+                        .text("Belt inner radius"),
                 );
                 ui.add(
-                    egui::Slider::new(&mut settings.max_planet_density, 0.5..=50.0)
-                        .text("Maximum planet density"),
+                    egui::Slider::new(&mut settings.belt_outer_radius, 100.0..=2000.0)
+                        .text("Belt outer radius"),
                 );
                 ui.add(
-                    egui::Slider::new(&mut settings.min_planet_orbit_radius, 100.0..=500.0)
-                        .text("Minimum planet orbit radius"),
+                    egui::Slider::new(&mut settings.belt_dispersion, 0.0..=0.2)
+                        .text("Belt velocity dispersion"),
                 );
+            }
+            if settings.scenario == Scenario::Resonances {
                 ui.add(
-                    egui::Slider::new(&mut settings.max_planet_orbit_radius, 500.0..=2000.0)
-                        .text("Maximum planet orbit radius"),
+                    egui::Slider::new(&mut settings.resonant_planet_orbit, 100.0..=1500.0)
+                        .text("Planet orbit radius"),
                 );
-                ui.add(egui::Slider::new(&mut settings.sun_size, 30.0..=100.0).text("Sun radius"));
                 ui.add(
-                    egui::Slider::new(&mut settings.sun_density, 5.0..=100.0).text("Sun density"),
+                    egui::Slider::new(&mut settings.resonance_particles, 1..=500)
+                        .text("Particles per resonance"),
                 );
-                if ui.button("Start").clicked() {
-                    ev_reset.send(Reset);
+                ui.horizontal_wrapped(|ui| {
+                    ui.label("Resonances");
+                    for ((p, q), enabled) in RESONANCES.iter().zip(settings.resonances.iter_mut()) {
+                        ui.checkbox(enabled, format!("{}:{}", p, q));
+                    }
+                });
+                ui.label("Collisions are disabled when the resonances start");
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            if settings.scenario == Scenario::Scene {
+                ui.horizontal(|ui| {
+                    ui.label("Scene file");
+                    if ui.text_edit_singleline(&mut settings.scene_path).changed() {
+                        settings.scene_code.clear();
+                    }
+                });
+            }
+            ui.horizontal(|ui| {
+                if ui.button("Copy scene").clicked() {
+                    ev_copy_scene.send(CopyScene);
                 }
+                if ui.button("Paste scene").clicked() {
+                    ev_paste_scene.send(PasteScene);
+                }
+                ui.text_edit_singleline(&mut stats.scene_code);
+            });
+            #[cfg(not(target_arch = "wasm32"))]
+            if ui.button("Duplicate simulation").clicked() {
+                stats.duplicate_pending = true;
+            }
+            if settings.scenario == Scenario::Ephemeris {
+                #[cfg(not(target_arch = "wasm32"))]
+                ui.horizontal(|ui| {
+                    ui.label("Ephemeris file");
+                    ui.text_edit_singleline(&mut settings.ephemeris_path);
+                });
+                ui.label("Leave the file empty for the bundled solar system");
+                ui.add(
+                    egui::Slider::new(&mut settings.ephemeris_distance_scale, 1.0..=200.0)
+                        .text("Units per AU"),
+                );
+                ui.add(
+                    egui::Slider::new(&mut settings.ephemeris_mass_scale, 1000.0..=10000000.0)
+                        .logarithmic(true)
+                        .text("Mass units per solar mass"),
+                );
+            }
+            ui.add(egui::Slider::new(&mut settings.sun_size, 30.0..=100.0).text("Sun radius"));
+            ui.add(egui::Slider::new(&mut settings.sun_density, 5.0..=100.0).text("Sun density"));
+            if ui.button("Start").clicked() {
+                ev_reset.send(Reset);
+            }
+        });
+    };
+    if floating {
+        let mut frame = egui::Frame::window(&ctx.style());
+        frame.fill = frame.fill.linear_multiply(opacity);
+        let window = egui::Window::new("Settings").frame(frame);
+        let window = if pinned {
+            window.fixed_pos(window_pos)
+        } else {
+            window.default_pos(window_pos)
+        };
+        if let Some(response) = window.show(ctx, contents) {
+            if pinned {
+                ctx.memory().areas.move_to_top(response.response.layer_id);
             }
+            stats.settings_window_pos = Some(response.response.rect.min);
         }
-    });
+    } else {
+        let mut frame = egui::Frame::side_top_panel(&ctx.style());
+        frame.fill = frame.fill.linear_multiply(opacity);
+        egui::SidePanel::left("settings")
+            .frame(frame)
+            .show(ctx, contents);
+    }
+}
+
+/// Value following `name` on the command line, e.g. the path in `--scene path.toml`.
+#[cfg(not(target_arch = "wasm32"))]
+fn argument(name: &str) -> Option<String> {
+    let mut args = std::env::args().skip_while(|arg| arg != name);
+    args.next()?;
+    args.next()
+}
+
+/// Window size given with `--width` and `--height`, `--fullscreen` covers the whole screen.
+#[cfg(not(target_arch = "wasm32"))]
+fn window_descriptor() -> WindowDescriptor {
+    let default = WindowDescriptor::default();
+    // logging isn't set up before the app runs
+    let size = |name: &str, default: f32| match argument(name).map(|value| value.parse::<f32>()) {
+        Some(Ok(size)) if size > 0.0 => size,
+        Some(_) => {
+            eprintln!(
+                "{} needs a positive number of pixels, using {}",
+                name, default
+            );
+            default
+        }
+        None => default,
+    };
+    let mode = if std::env::args().any(|arg| arg == "--fullscreen") {
+        WindowMode::BorderlessFullscreen
+    } else {
+        WindowMode::Windowed
+    };
+    WindowDescriptor {
+        width: size("--width", default.width),
+        height: size("--height", default.height),
+        mode,
+        ..default
+    }
 }
 
 #[wasm_bindgen]
 pub fn game() {
     #[cfg(target_arch = "wasm32")]
     {
+        let mut settings = Settings::default();
+        url_params::apply_url_parameters(&mut settings);
         App::new()
             .insert_resource(Msaa { samples: 4 })
             .insert_resource(ClearColor(Color::rgb(0.0, 0.0, 0.0)))
-            .insert_resource(Settings::default())
+            .init_resource::<clipboard::ClipboardInbox>()
+            .insert_resource(settings)
             .add_event::<ClearTraces>()
             .add_event::<Reset>()
+            .add_event::<Launch>()
+            .add_event::<Predict>()
+            .add_event::<Stir>()
+            .add_event::<CopyScene>()
+            .add_event::<PasteScene>()
+            .add_event::<ChangePlanetCount>()
             .add_plugins(DefaultPlugins)
             .add_plugin(FrameTimeDiagnosticsPlugin::default())
+            .add_plugin(EntityCountDiagnosticsPlugin::default())
             .add_plugin(EguiPlugin)
             .add_plugin(ShapePlugin)
+            .add_plugin(QuadsPlugin::default())
             .add_plugin(FlyCameraPlugin)
             .add_plugin(PanCamPlugin::default())
+            .add_plugin(GridPlugin::default())
+            .add_plugin(MinimapPlugin::default())
+            .add_plugin(CrosshairsPlugin::default())
+            .add_plugin(BinaryPlugin::default())
+            .add_plugin(HillSpherePlugin::default())
+            .add_plugin(MomentumPlugin::default())
+            .add_plugin(LabelsPlugin::default())
+            .add_plugin(EscapePlugin::default())
+            .add_plugin(StarFieldPlugin::default())
+            .add_plugin(PointsPlugin::default())
+            .add_plugin(GlowPlugin::default())
+            .add_plugin(LensingPlugin::default())
+            .add_plugin(ScreenshotPlugin::default())
+            .add_plugin(ReplayPlugin::default())
             .add_plugin(bevy_web_resizer::Plugin)
             .add_startup_system(setup)
-            .add_system(gravity)
+            .add_system(rescale_on_g_change.before(SimulationLabel::Gravity))
+            .add_system(gravity.label(SimulationLabel::Gravity))
+            .add_system(
+                classify_orbits
+                    .label(SimulationLabel::ClassifyOrbits)
+                    .after(SimulationLabel::Gravity),
+            )
+            .add_system(outline_acceleration.after(SimulationLabel::ClassifyOrbits))
             .add_system(ui_box)
             .add_system(move_camera)
+            .add_system(apply_perspective_tilt)
+            .add_system(apply_body_shape)
+            .add_system(apply_msaa)
+            .add_system(select_planet)
+            .add_system(track_cursor)
+            .add_system(focus_selected)
+            .add_system(focus_collision)
+            .add_system(place_anchor)
+            .add_system(launch_planet)
+            .add_system(change_planet_count)
+            .add_system(preview_launch)
+            .add_system(predict_paths)
+            .add_system(copy_scene)
+            .add_system(paste_scene)
+            .add_system(stir_planets.before(SimulationLabel::Gravity))
+            .add_system(nudge_hovered_planet.before(SimulationLabel::Gravity))
+            .add_system(cool_planets.after(SimulationLabel::Gravity))
+            .add_system(time_step_keys)
+            .add_system(time_step_notice)
+            .add_system(limit_bodies)
+            .add_system(auto_scale_bodies)
             .add_system(despawn_traces)
-            .add_system(setup_many_orbits)
+            .add_system(fade_traces)
+            .add_system(animate_flashes)
+            .add_system(animate_merges)
+            .add_system(draw_arena)
+            // run after gravity so the bodies spawned on reset are only seen on the next frame
+            .add_system(setup_many_orbits.after(SimulationLabel::Gravity))
             .insert_resource(Stats::default())
+            .insert_resource(BodyIdCounter::default())
+            .insert_resource(MergeLog::default())
+            .add_system_to_stage(CoreStage::PreUpdate, assign_body_ids)
             .run();
     }
     #[cfg(not(target_arch = "wasm32"))]
     {
+        let mut settings = Settings::default();
+        if let Some(path) = argument("--scene") {
+            // "-" reads the settings and the scene code from the standard input, see
+            // `duplicate_simulation`
+            if path == "-" {
+                let mut input = String::new();
+                let read = std::io::Read::read_to_string(&mut std::io::stdin(), &mut input)
+                    .map_err(|err| err.to_string())
+                    .and_then(|_| {
+                        serde_json::from_str::<Settings>(&input).map_err(|err| err.to_string())
+                    });
+                match read {
+                    Ok(duplicated) => settings = duplicated,
+                    // logging isn't set up before the app runs
+                    Err(err) => eprintln!("can't read the duplicated settings: {}", err),
+                }
+            } else {
+                settings.scene_path = path;
+            }
+            settings.scenario = Scenario::Scene;
+        }
         App::new()
             .insert_resource(Msaa { samples: 4 })
             .insert_resource(ClearColor(Color::rgb(0.0, 0.0, 0.0)))
-            .insert_resource(Settings::default())
+            .insert_resource(settings)
+            .insert_resource(window_descriptor())
             .add_event::<ClearTraces>()
             .add_event::<Reset>()
+            .add_event::<Launch>()
+            .add_event::<Predict>()
+            .add_event::<Stir>()
+            .add_event::<CopyScene>()
+            .add_event::<PasteScene>()
+            .add_event::<ChangePlanetCount>()
             .add_plugins(DefaultPlugins)
             .add_plugin(FrameTimeDiagnosticsPlugin::default())
+            .add_plugin(EntityCountDiagnosticsPlugin::default())
             .add_plugin(EguiPlugin)
             .add_plugin(ShapePlugin)
+            .add_plugin(QuadsPlugin::default())
             .add_plugin(FlyCameraPlugin)
             .add_plugin(PanCamPlugin::default())
+            .add_plugin(GridPlugin::default())
+            .add_plugin(MinimapPlugin::default())
+            .add_plugin(CrosshairsPlugin::default())
+            .add_plugin(BinaryPlugin::default())
+            .add_plugin(HillSpherePlugin::default())
+            .add_plugin(MomentumPlugin::default())
+            .add_plugin(LabelsPlugin::default())
+            .add_plugin(EscapePlugin::default())
+            .add_plugin(StarFieldPlugin::default())
+            .add_plugin(PointsPlugin::default())
+            .add_plugin(GlowPlugin::default())
+            .add_plugin(LensingPlugin::default())
+            .add_plugin(ScreenshotPlugin::default())
+            .add_plugin(ReplayPlugin::default())
             .add_startup_system(setup)
-            .add_system(gravity)
+            .add_system(rescale_on_g_change.before(SimulationLabel::Gravity))
+            .add_system(gravity.label(SimulationLabel::Gravity))
+            .add_system(
+                classify_orbits
+                    .label(SimulationLabel::ClassifyOrbits)
+                    .after(SimulationLabel::Gravity),
+            )
+            .add_system(outline_acceleration.after(SimulationLabel::ClassifyOrbits))
             .add_system(ui_box)
             .add_system(move_camera)
+            .add_system(apply_perspective_tilt)
+            .add_system(apply_body_shape)
+            .add_system(apply_msaa)
+            .add_system(select_planet)
+            .add_system(track_cursor)
+            .add_system(focus_selected)
+            .add_system(focus_collision)
+            .add_system(place_anchor)
+            .add_system(launch_planet)
+            .add_system(change_planet_count)
+            .add_system(preview_launch)
+            .add_system(predict_paths)
+            .add_system(copy_scene)
+            .add_system(paste_scene)
+            .add_system(stir_planets.before(SimulationLabel::Gravity))
+            .add_system(nudge_hovered_planet.before(SimulationLabel::Gravity))
+            .add_system(cool_planets.after(SimulationLabel::Gravity))
+            .add_system(time_step_keys)
+            .add_system(time_step_notice)
+            .add_system(limit_bodies)
+            .add_system(auto_scale_bodies)
             .add_system(despawn_traces)
-            .add_system(setup_many_orbits)
+            .add_system(fade_traces)
+            .add_system(animate_flashes)
+            .add_system(animate_merges)
+            .add_system(draw_arena)
+            .add_system(apply_vsync)
+            .add_system(duplicate_simulation)
+            .add_plugin(DiagnosticsLogPlugin::default())
+            .add_plugin(MergeLogPlugin::default())
+            .add_system_to_stage(CoreStage::Last, limit_frame_rate)
+            // run after gravity so the bodies spawned on reset are only seen on the next frame
+            .add_system(setup_many_orbits.after(SimulationLabel::Gravity))
             .insert_resource(Stats::default())
+            .insert_resource(BodyIdCounter::default())
+            .insert_resource(MergeLog::default())
+            .add_system_to_stage(CoreStage::PreUpdate, assign_body_ids)
             .run();
     }
 }
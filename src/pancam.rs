@@ -1,3 +1,4 @@
+use crate::Settings;
 use bevy::{
     input::mouse::{MouseScrollUnit, MouseWheel},
     prelude::*,
@@ -13,14 +14,25 @@ pub struct PanCamPlugin;
 
 impl Plugin for PanCamPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(camera_movement).add_system(camera_zoom);
+        app.add_system(camera_movement)
+            .add_system(camera_zoom)
+            .add_system(camera_focus);
     }
 }
 
+/// Exponential decay rate of the coasting speed after the mouse is released, per second
+const PAN_DAMPING: f32 = 4.0;
+/// Coasting stops below this speed in pixels per second
+const MIN_COAST_SPEED: f32 = 5.0;
+/// Exponential rate at which the camera approaches the point it focuses on, per second
+const FOCUS_RATE: f32 = 6.0;
+
 // Zoom doesn't work on bevy 0.5 due to: https://github.com/bevyengine/bevy/pull/2015
 fn camera_zoom(
     mut egui_ctx: ResMut<EguiContext>,
-    mut query: Query<(&PanCam, &mut OrthographicProjection)>,
+    windows: Res<Windows>,
+    settings: Res<Settings>,
+    mut query: Query<(&mut PanCam, &mut Transform, &mut OrthographicProjection)>,
     mut scroll_events: EventReader<MouseWheel>,
 ) {
     if egui_ctx.ctx_mut().wants_pointer_input()
@@ -43,8 +55,22 @@ fn camera_zoom(
         return;
     }
 
-    for (_cam, mut projection) in query.iter_mut() {
-        projection.scale = (projection.scale * (1. + -scroll * 0.001)).max(0.00001);
+    let window = windows.get_primary();
+    for (mut cam, mut transform, mut projection) in query.iter_mut() {
+        cam.focus = None;
+        let old_scale = projection.scale;
+        projection.scale =
+            (projection.scale * (1. + -scroll * 0.001)).clamp(settings.min_zoom, settings.max_zoom);
+        // keep the world point under the cursor in place
+        if let Some(cursor) = window.and_then(|window| {
+            let window_size = Vec2::new(window.width(), window.height());
+            window
+                .cursor_position()
+                .map(|cursor| cursor - window_size / 2.0)
+        }) {
+            let shift = cursor * transform.scale.truncate() * (old_scale - projection.scale);
+            transform.translation += shift.extend(0.);
+        }
     }
 }
 
@@ -52,51 +78,121 @@ fn camera_movement(
     mut egui_ctx: ResMut<EguiContext>,
     mut windows: ResMut<Windows>,
     mouse_buttons: Res<Input<MouseButton>>,
-    mut query: Query<(&PanCam, &mut Transform, &OrthographicProjection)>,
+    time: Res<Time>,
+    mut query: Query<(&mut PanCam, &mut Transform, &OrthographicProjection)>,
     mut last_pos: Local<Option<Vec2>>,
 ) {
-    if egui_ctx.ctx_mut().wants_pointer_input()
+    let ui_blocked = egui_ctx.ctx_mut().wants_pointer_input()
         || egui_ctx.ctx_mut().wants_keyboard_input()
-        || egui_ctx.ctx_mut().is_pointer_over_area()
-    {
-        return;
-    }
+        || egui_ctx.ctx_mut().is_pointer_over_area();
 
     let window = windows.get_primary_mut().unwrap();
+    let dt = time.delta_seconds();
 
     // Use position instead of MouseMotion, otherwise we don't get acceleration movement
-    let current_pos = match window.cursor_position() {
-        Some(current_pos) => current_pos,
-        None => return,
+    let current_pos = window.cursor_position();
+    let delta = match (current_pos, *last_pos) {
+        (Some(current_pos), Some(last_pos)) if !ui_blocked => current_pos - last_pos,
+        _ => Vec2::ZERO,
     };
-    let delta = current_pos - last_pos.unwrap_or(current_pos);
-
-    for (cam, mut transform, projection) in query.iter_mut() {
-        if cam
-            .grab_buttons
-            .iter()
-            .any(|btn| mouse_buttons.pressed(*btn))
-        {
-            let scaling = Vec2::new(
-                window.width() / (projection.right - projection.left),
-                window.height() / (projection.top - projection.bottom),
-            ) * projection.scale;
 
+    for (mut cam, mut transform, projection) in query.iter_mut() {
+        let scaling = Vec2::new(
+            window.width() / (projection.right - projection.left),
+            window.height() / (projection.top - projection.bottom),
+        ) * projection.scale
+            * transform.scale.truncate();
+        let grabbed = !ui_blocked
+            && current_pos.is_some()
+            && cam
+                .grab_buttons
+                .iter()
+                .any(|btn| mouse_buttons.pressed(*btn));
+
+        if grabbed {
+            cam.focus = None;
             transform.translation -= (delta * scaling).extend(0.);
+            if dt > 0.0 {
+                cam.velocity = -delta / dt;
+            }
+        } else if cam.inertia && cam.velocity.length() > MIN_COAST_SPEED {
+            transform.translation += (cam.velocity * dt * scaling).extend(0.);
+            cam.velocity *= (-PAN_DAMPING * dt).exp();
+        } else {
+            cam.velocity = Vec2::ZERO;
         }
     }
-    *last_pos = Some(current_pos);
+    *last_pos = current_pos;
 }
 
 #[derive(Component)]
 pub struct PanCam {
     grab_buttons: Vec<MouseButton>,
+    /// Keep moving after the mouse is released, slowing down gradually
+    pub inertia: bool,
+    /// Screen space velocity of the camera in pixels per second
+    velocity: Vec2,
+    /// Position and zoom the camera is moving towards, see `focus`
+    focus: Option<(Vec2, f32)>,
+}
+
+impl PanCam {
+    /// Smoothly moves the camera to `position` and zooms to `scale` over the next frames,
+    /// dragging or zooming with the mouse cancels it.
+    pub fn focus(&mut self, position: Vec2, scale: f32) {
+        self.focus = Some((position, scale));
+        self.velocity = Vec2::ZERO;
+    }
 }
 
 impl Default for PanCam {
     fn default() -> Self {
         Self {
             grab_buttons: vec![MouseButton::Left, MouseButton::Right, MouseButton::Middle],
+            inertia: true,
+            velocity: Vec2::ZERO,
+            focus: None,
         }
     }
 }
+
+fn camera_focus(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    mut query: Query<(&mut PanCam, &mut Transform, &mut OrthographicProjection)>,
+) {
+    let step = 1.0 - (-FOCUS_RATE * time.delta_seconds()).exp();
+    for (mut cam, mut transform, mut projection) in query.iter_mut() {
+        let (position, scale) = match cam.focus {
+            Some(focus) => focus,
+            None => continue,
+        };
+        let scale = scale.clamp(settings.min_zoom, settings.max_zoom);
+        let current = transform.translation.truncate();
+        let mut next = current.lerp(position, step);
+        let mut next_scale = projection.scale + (scale - projection.scale) * step;
+        // stop once the remaining move is below a pixel
+        if next.distance(position) < next_scale && (next_scale / scale - 1.0).abs() < 0.01 {
+            next = position;
+            next_scale = scale;
+            cam.focus = None;
+        }
+        transform.translation.x = next.x;
+        transform.translation.y = next.y;
+        projection.scale = next_scale;
+    }
+}
+
+/// Converts the cursor position in the window into world coordinates seen by the camera.
+pub fn cursor_to_world(
+    window: &Window,
+    transform: &Transform,
+    projection: &OrthographicProjection,
+) -> Option<Vec2> {
+    let cursor = window.cursor_position()?;
+    let window_size = Vec2::new(window.width(), window.height());
+    Some(
+        transform.translation.truncate()
+            + (cursor - window_size / 2.0) * projection.scale * transform.scale.truncate(),
+    )
+}
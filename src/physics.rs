@@ -0,0 +1,689 @@
+//! Simulation core working on plain structs so it can be used without Bevy ECS.
+use bevy::math::Vec2;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+/// A point mass with a finite radius used for collisions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Body {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub mass: f32,
+    pub radius: f32,
+    /// Fixed bodies attract the others but never move and never collide
+    pub fixed: bool,
+    /// Suns and planets can have different `MergePolicy` rules
+    pub is_sun: bool,
+}
+
+/// Two bodies (indices into the slice passed to `compute_forces`) which touched each other.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Collision {
+    pub first: usize,
+    pub second: usize,
+}
+
+/// Result of a single force computation pass.
+#[derive(Debug, Default)]
+pub struct Forces {
+    pub accelerations: Vec<Vec2>,
+    pub collisions: Vec<Collision>,
+    /// `true` for every body consumed by a collision during this pass
+    pub merged: Vec<bool>,
+    /// Indices of the two closest bodies and the distance between their centers
+    pub closest: Option<(usize, usize, f32)>,
+}
+
+/// How the radius of a merged body is derived from its parents.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum MergeDimension {
+    /// Bodies are treated as flat disks, merged radius preserves the total area
+    Area,
+    /// Bodies are treated as spheres, merged radius preserves the total volume
+    Volume,
+}
+
+/// How the gravity softening length of a pair of bodies is chosen.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum SofteningMode {
+    /// The same length for every pair
+    Constant,
+    /// Proportional to the size of the pair, sqrt(r₁² + r₂²)
+    RadiusScaled,
+}
+
+/// Which kinds of bodies merge when they touch, the others pass through each other.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MergePolicy {
+    pub planets_with_planets: bool,
+    pub planets_with_suns: bool,
+    pub suns_with_suns: bool,
+}
+
+impl Default for MergePolicy {
+    fn default() -> Self {
+        Self {
+            planets_with_planets: true,
+            planets_with_suns: true,
+            suns_with_suns: true,
+        }
+    }
+}
+
+impl MergePolicy {
+    pub fn allows(&self, body_1: &Body, body_2: &Body) -> bool {
+        match (body_1.is_sun, body_2.is_sun) {
+            (false, false) => self.planets_with_planets,
+            (true, true) => self.suns_with_suns,
+            _ => self.planets_with_suns,
+        }
+    }
+}
+
+/// Parameters of the force computation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ForceParams {
+    pub g: f32,
+    pub collisions: bool,
+    /// Kinds of bodies which merge when `collisions` are enabled
+    pub merge_policy: MergePolicy,
+    /// Coefficient of the extra `g·m/r⁴` attraction approximating relativistic
+    /// perihelion precession, zero disables it
+    pub gr_correction: f32,
+    /// Bodies touch when their centers are closer than this fraction of the sum of their radii
+    pub merge_overlap: f32,
+    /// When set only bodies at least this massive attract the others, lighter ones are test
+    /// particles which neither attract nor collide with each other
+    pub test_particle_mass: Option<f32>,
+    pub softening_mode: SofteningMode,
+    /// Softening length with `SofteningMode::Constant`, factor of the pair size with
+    /// `SofteningMode::RadiusScaled`, zero disables the softening
+    pub softening: f32,
+    /// Find the collisions with `grid_collisions` instead of checking every pair
+    pub collision_grid: bool,
+}
+
+/// Time integration scheme.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Integrator {
+    /// Positions are advanced with the old velocities, first order and the energy grows
+    /// with every orbit, only useful to show how much better the others are
+    ExplicitEuler,
+    /// Velocities and positions are synchronized at the start of the simulation
+    SemiImplicitEuler,
+    /// Same update but the velocities are kept half a step behind the positions,
+    /// which makes it second order and time reversible
+    Leapfrog,
+}
+
+/// Naive all pairs gravity. When `collisions` are enabled touching bodies are reported as
+/// collisions and stop interacting with the rest of the system for this pass, otherwise
+/// (and always for fixed bodies) the distance is clamped to the touching distance so the force
+/// stays bounded. Bodies touch within `merge_overlap` times the sum of their radii.
+/// With softening the attraction follows the Plummer form g·m·r / (r² + ε²)^(3/2).
+pub fn compute_forces(bodies: &[Body], params: &ForceParams) -> Forces {
+    let g = params.g;
+    let collisions = params.collisions;
+    let mut forces = Forces {
+        accelerations: vec![Vec2::ZERO; bodies.len()],
+        collisions: Vec::new(),
+        merged: vec![false; bodies.len()],
+        closest: None,
+    };
+
+    let is_source: Vec<bool> = match params.test_particle_mass {
+        Some(threshold) => bodies.iter().map(|body| body.mass >= threshold).collect(),
+        None => vec![true; bodies.len()],
+    };
+    let sources: Vec<usize> = (0..bodies.len()).filter(|&j| is_source[j]).collect();
+    if collisions && params.collision_grid {
+        grid_collisions(bodies, &is_source, params, &mut forces);
+    }
+
+    for (i, body_1) in bodies.iter().enumerate() {
+        let mut accel_cum = Vec2::new(0.0, 0.0);
+        for &j in sources.iter() {
+            let body_2 = &bodies[j];
+            if i != j && !forces.merged[i] && !forces.merged[j] {
+                let r_vector = body_1.position - body_2.position;
+                // every pair is visited once, test particles only meet the sources
+                if i < j || !is_source[i] {
+                    let distance = r_vector.length();
+                    if forces.closest.map_or(true, |(_, _, min)| distance < min) {
+                        forces.closest = Some((i, j, distance));
+                    }
+                }
+                let touching_distance = params.merge_overlap * (body_1.radius + body_2.radius);
+                let touching = r_vector.length() < touching_distance;
+                if touching
+                    && collisions
+                    && !params.collision_grid
+                    && !body_1.fixed
+                    && !body_2.fixed
+                    && params.merge_policy.allows(body_1, body_2)
+                {
+                    forces.merged[i] = true;
+                    forces.merged[j] = true;
+                    forces.collisions.push(Collision {
+                        first: i,
+                        second: j,
+                    });
+                } else {
+                    let r_mag = r_vector.length();
+                    let r_mag = if touching { touching_distance } else { r_mag };
+                    let mut accel: f32 = if params.softening > 0.0 {
+                        let epsilon = match params.softening_mode {
+                            SofteningMode::Constant => params.softening,
+                            SofteningMode::RadiusScaled => {
+                                params.softening
+                                    * (body_1.radius.powi(2) + body_2.radius.powi(2)).sqrt()
+                            }
+                        };
+                        -1.0 * g * body_2.mass * r_mag / (r_mag.powi(2) + epsilon.powi(2)).powf(1.5)
+                    } else {
+                        -1.0 * g * body_2.mass / r_mag.powf(2.0)
+                    };
+                    if params.gr_correction != 0.0 {
+                        accel -= params.gr_correction * g * body_2.mass / r_mag.powf(4.0);
+                    }
+                    let r_vector_unit = r_vector / r_mag;
+                    accel_cum += accel * r_vector_unit;
+                }
+            }
+        }
+        forces.accelerations[i] = accel_cum;
+    }
+
+    forces
+}
+
+/// Collision detection on a uniform grid with cells as large as the largest touching
+/// distance, so only the bodies in neighboring cells are compared. The grid is rebuilt on
+/// every call. Finds the same collisions in the same order as the all pairs check of
+/// `compute_forces`, which is what makes the two interchangeable.
+fn grid_collisions(bodies: &[Body], is_source: &[bool], params: &ForceParams, forces: &mut Forces) {
+    let largest_radius = bodies
+        .iter()
+        .filter(|body| !body.fixed)
+        .fold(0.0f32, |largest, body| largest.max(body.radius));
+    let cell_size = 2.0 * largest_radius * params.merge_overlap;
+    if cell_size <= 0.0 || !cell_size.is_finite() {
+        return;
+    }
+    let cell_of = |position: Vec2| {
+        (
+            (position.x / cell_size).floor() as i64,
+            (position.y / cell_size).floor() as i64,
+        )
+    };
+    // only the sources collide, fixed bodies never do
+    let mut grid: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+    for (j, body) in bodies.iter().enumerate() {
+        if is_source[j] && !body.fixed {
+            grid.entry(cell_of(body.position)).or_default().push(j);
+        }
+    }
+
+    let mut candidates = Vec::new();
+    for (i, body_1) in bodies.iter().enumerate() {
+        if forces.merged[i] || body_1.fixed {
+            continue;
+        }
+        let (x, y) = cell_of(body_1.position);
+        candidates.clear();
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                if let Some(cell) = grid.get(&(x + dx, y + dy)) {
+                    candidates.extend(cell.iter().copied());
+                }
+            }
+        }
+        // two sources meet when the first one is visited, test particles meet every source
+        candidates.retain(|&j| j > i || (j != i && !is_source[i]));
+        candidates.sort_unstable();
+        for &j in candidates.iter() {
+            let body_2 = &bodies[j];
+            if forces.merged[j] {
+                continue;
+            }
+            let touching_distance = params.merge_overlap * (body_1.radius + body_2.radius);
+            let touching = (body_1.position - body_2.position).length() < touching_distance;
+            if touching && params.merge_policy.allows(body_1, body_2) {
+                forces.merged[i] = true;
+                forces.merged[j] = true;
+                forces.collisions.push(Collision {
+                    first: i,
+                    second: j,
+                });
+                break;
+            }
+        }
+    }
+}
+
+/// Adds the drag of a gas disk circling the heaviest body, -k·(v - v_gas), to the accelerations.
+/// The gas moves on circular orbits in the same direction as the body, so the drag damps
+/// the difference from a circular orbit: orbits decay and circularize like in a protoplanetary
+/// disk. A plain -k·v would instead make them more eccentric as they decay.
+pub fn apply_gas_drag(bodies: &[Body], forces: &mut Forces, g: f32, k: f32) {
+    let center = match bodies
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.mass.total_cmp(&b.mass))
+    {
+        Some((center, _)) => center,
+        None => return,
+    };
+    let mu = g * bodies[center].mass;
+    for (i, body) in bodies.iter().enumerate() {
+        if i == center || forces.merged[i] || body.fixed {
+            continue;
+        }
+        let position = body.position - bodies[center].position;
+        let velocity = body.velocity - bodies[center].velocity;
+        let distance = position.length();
+        let gas_velocity = if distance > 0.0 && mu > 0.0 {
+            // prograde with the orbit of the body
+            let direction = position.perp() / distance * position.perp_dot(velocity).signum();
+            direction * (mu / distance).sqrt()
+        } else {
+            Vec2::ZERO
+        };
+        forces.accelerations[i] -= k * (velocity - gas_velocity);
+    }
+}
+
+/// Semi-implicit Euler step, fixed bodies and bodies consumed by a collision are left untouched.
+pub fn integrate(bodies: &mut [Body], forces: &Forces, dt: f32) {
+    for (i, body) in bodies.iter_mut().enumerate() {
+        if !forces.merged[i] && !body.fixed {
+            body.velocity += forces.accelerations[i] * dt;
+            body.position += body.velocity * dt;
+        }
+    }
+}
+
+/// Advances the bodies by one step of the given integrator. Leapfrog additionally needs
+/// its velocities moved half a step back with `leapfrog_start` before the first step.
+pub fn advance(bodies: &mut [Body], forces: &Forces, dt: f32, integrator: Integrator) {
+    match integrator {
+        Integrator::ExplicitEuler => {
+            for (i, body) in bodies.iter_mut().enumerate() {
+                if !forces.merged[i] && !body.fixed {
+                    body.position += body.velocity * dt;
+                    body.velocity += forces.accelerations[i] * dt;
+                }
+            }
+        }
+        Integrator::SemiImplicitEuler | Integrator::Leapfrog => integrate(bodies, forces, dt),
+    }
+}
+
+/// Positions of the bodies over the next `steps` semi-implicit Euler steps, sampled every
+/// `sample_every` steps, computed on a copy so the bodies themselves are left untouched.
+/// The path of a body absorbed in a collision ends there, the heavier one carries on with
+/// the merged mass.
+pub fn predict_paths(
+    bodies: &[Body],
+    params: &ForceParams,
+    dt: f32,
+    steps: usize,
+    sample_every: usize,
+    merge_dimension: MergeDimension,
+) -> Vec<Vec<Vec2>> {
+    let mut scratch = bodies.to_vec();
+    // index in `bodies` of every body still in `scratch`
+    let mut origins: Vec<usize> = (0..bodies.len()).collect();
+    let mut paths: Vec<Vec<Vec2>> = bodies.iter().map(|body| vec![body.position]).collect();
+    for step in 1..=steps {
+        let forces = compute_forces(&scratch, params);
+        integrate(&mut scratch, &forces, dt);
+        let mut absorbed = Vec::new();
+        for collision in forces.collisions.iter() {
+            let (first, second) = (scratch[collision.first], scratch[collision.second]);
+            let (survivor, lost) = if first.mass >= second.mass {
+                (collision.first, collision.second)
+            } else {
+                (collision.second, collision.first)
+            };
+            scratch[survivor] = Body {
+                position: scratch[survivor].position,
+                velocity: merge_velocity(&first, &second),
+                mass: first.mass + second.mass,
+                radius: merge_radius(first.radius, second.radius, merge_dimension),
+                fixed: false,
+                is_sun: first.is_sun || second.is_sun,
+            };
+            paths[origins[lost]].push(scratch[survivor].position);
+            absorbed.push(lost);
+        }
+        absorbed.sort_unstable();
+        for lost in absorbed.into_iter().rev() {
+            scratch.remove(lost);
+            origins.remove(lost);
+        }
+        if step % sample_every == 0 {
+            for (body, origin) in scratch.iter().zip(origins.iter()) {
+                paths[*origin].push(body.position);
+            }
+        }
+    }
+    paths
+}
+
+/// Pushes overlapping bodies apart along the line between their centers by `strength` times
+/// the penetration depth, split so the lighter body moves more and fixed bodies don't move.
+/// Pairs which would merge under `params` are left alone, as are bodies consumed by a collision.
+pub fn resolve_overlaps(bodies: &mut [Body], forces: &Forces, params: &ForceParams, strength: f32) {
+    for i in 0..bodies.len() {
+        for j in i + 1..bodies.len() {
+            let (body_1, body_2) = (bodies[i], bodies[j]);
+            if forces.merged[i] || forces.merged[j] || (body_1.fixed && body_2.fixed) {
+                continue;
+            }
+            if params.collisions && params.merge_policy.allows(&body_1, &body_2) {
+                continue;
+            }
+            let offset = body_2.position - body_1.position;
+            let distance = offset.length();
+            let penetration = body_1.radius + body_2.radius - distance;
+            if penetration <= 0.0 {
+                continue;
+            }
+            // coincident centers have no direction, any one will do
+            let normal = if distance > 0.0 {
+                offset / distance
+            } else {
+                Vec2::X
+            };
+            let share_1 = match (body_1.fixed, body_2.fixed) {
+                (true, _) => 0.0,
+                (_, true) => 1.0,
+                _ if body_1.mass + body_2.mass > 0.0 => body_2.mass / (body_1.mass + body_2.mass),
+                _ => 0.5,
+            };
+            let correction = normal * penetration * strength;
+            bodies[i].position -= correction * share_1;
+            bodies[j].position += correction * (1.0 - share_1);
+        }
+    }
+}
+
+/// Keeps the body inside the rectangle `-half_size..half_size` around the origin by elastic
+/// reflections off its walls, touching them with its edge rather than its center. The axes are
+/// independent so a body reaching a corner bounces off both walls in the same step, and a step
+/// long enough to cross the arena is folded back as many times as needed.
+pub fn bounce_off_walls(body: &mut Body, half_size: Vec2) {
+    let limit = (half_size - Vec2::splat(body.radius)).max(Vec2::ZERO);
+    body.position.x = reflect_into(body.position.x, &mut body.velocity.x, limit.x);
+    body.position.y = reflect_into(body.position.y, &mut body.velocity.y, limit.y);
+}
+
+/// Folds `position` into `-limit..limit`, the velocity is flipped after an odd number of
+/// reflections.
+fn reflect_into(position: f32, velocity: &mut f32, limit: f32) -> f32 {
+    if limit <= 0.0 {
+        // the body doesn't fit between the walls
+        *velocity = 0.0;
+        return 0.0;
+    }
+    let folded = (position + limit).rem_euclid(4.0 * limit);
+    if folded <= 2.0 * limit {
+        folded - limit
+    } else {
+        *velocity = -*velocity;
+        3.0 * limit - folded
+    }
+}
+
+/// Conserved quantities of the whole system, computed by `system_totals`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SystemTotals {
+    pub mass: f32,
+    /// Center of mass, origin when there is no mass at all
+    pub barycenter: Vec2,
+    /// Total linear momentum, sum of m·v
+    pub momentum: Vec2,
+    /// Z component of the total angular momentum about the barycenter
+    pub angular_momentum: f32,
+}
+
+/// Computes mass, barycenter, momentum and angular momentum in a single pass over the bodies.
+pub fn system_totals(bodies: &[Body]) -> SystemTotals {
+    let mut mass = 0.0;
+    let mut moment = Vec2::ZERO;
+    let mut momentum = Vec2::ZERO;
+    let mut angular_momentum_origin = 0.0;
+    for body in bodies {
+        mass += body.mass;
+        moment += body.position * body.mass;
+        momentum += body.velocity * body.mass;
+        angular_momentum_origin += body.mass * body.position.perp_dot(body.velocity);
+    }
+    if mass <= 0.0 {
+        return SystemTotals::default();
+    }
+    let barycenter = moment / mass;
+    SystemTotals {
+        mass,
+        barycenter,
+        momentum,
+        // shift the reference point from the origin to the barycenter: L' = L - R × P
+        angular_momentum: angular_momentum_origin - barycenter.perp_dot(momentum),
+    }
+}
+
+/// Kinetic plus potential energy of the system. Like in `compute_forces` the distance of
+/// touching bodies is clamped to the sum of their radii.
+pub fn total_energy(bodies: &[Body], g: f32) -> f32 {
+    let mut energy = 0.0;
+    for (i, body_1) in bodies.iter().enumerate() {
+        energy += 0.5 * body_1.mass * body_1.velocity.length_squared();
+        for body_2 in bodies[i + 1..].iter() {
+            let distance = body_1
+                .position
+                .distance(body_2.position)
+                .max(body_1.radius + body_2.radius);
+            energy -= g * body_1.mass * body_2.mass / distance;
+        }
+    }
+    energy
+}
+
+/// Scales the velocities around the mean velocity of the moving bodies so that the total
+/// energy becomes `target`, keeping the momentum. Not physical, it only hides the energy
+/// drift of the integrator. Returns `false` when no scaling reaches the target.
+pub fn rescale_energy(bodies: &mut [Body], g: f32, target: f32) -> bool {
+    let (mass, momentum) = bodies
+        .iter()
+        .filter(|body| !body.fixed)
+        .fold((0.0, Vec2::ZERO), |(mass, momentum), body| {
+            (mass + body.mass, momentum + body.velocity * body.mass)
+        });
+    if mass <= 0.0 {
+        return false;
+    }
+    let mean_velocity = momentum / mass;
+    // only the kinetic energy of the motion relative to the mean velocity can change
+    let internal_kinetic: f32 = bodies
+        .iter()
+        .filter(|body| !body.fixed)
+        .map(|body| 0.5 * body.mass * (body.velocity - mean_velocity).length_squared())
+        .sum();
+    let needed_kinetic = internal_kinetic + target - total_energy(bodies, g);
+    if internal_kinetic <= 0.0 || needed_kinetic <= 0.0 {
+        return false;
+    }
+    let factor = (needed_kinetic / internal_kinetic).sqrt();
+    for body in bodies.iter_mut().filter(|body| !body.fixed) {
+        body.velocity = mean_velocity + (body.velocity - mean_velocity) * factor;
+    }
+    true
+}
+
+/// Moves synchronized initial velocities half a step back, v(-dt/2) = v(0) - a(0)·dt/2,
+/// so that the following `integrate` calls act as a leapfrog scheme.
+pub fn leapfrog_start(bodies: &mut [Body], forces: &Forces, dt: f32) {
+    for (i, body) in bodies.iter_mut().enumerate() {
+        if !forces.merged[i] && !body.fixed {
+            body.velocity -= forces.accelerations[i] * dt / 2.0;
+        }
+    }
+}
+
+/// Velocity of the body created by a perfectly inelastic collision (momentum is conserved).
+pub fn merge_velocity(body_1: &Body, body_2: &Body) -> Vec2 {
+    let sum_mass = body_1.mass + body_2.mass;
+    body_1.velocity * body_1.mass / sum_mass + body_2.velocity * body_2.mass / sum_mass
+}
+
+/// Center of mass of two merging bodies, the merged body keeps the barycenter in place.
+pub fn merge_position(body_1: &Body, body_2: &Body) -> Vec2 {
+    let sum_mass = body_1.mass + body_2.mass;
+    body_1.position * body_1.mass / sum_mass + body_2.position * body_2.mass / sum_mass
+}
+
+/// Kinetic energy lost in a perfectly inelastic collision, the energy of the relative
+/// motion: ½ · m₁m₂/(m₁+m₂) · |v₁ - v₂|².
+pub fn collision_heat(body_1: &Body, body_2: &Body) -> f32 {
+    let reduced_mass = body_1.mass * body_2.mass / (body_1.mass + body_2.mass);
+    0.5 * reduced_mass * (body_1.velocity - body_2.velocity).length_squared()
+}
+
+/// Radius of the body merged from two bodies. By volume the radius grows with the cube
+/// root of the total, slower than with the square root by area: two equal spheres merge
+/// into one 2^(1/3) ≈ 1.26 times larger, two equal disks into one √2 ≈ 1.41 times larger.
+pub fn merge_radius(radius_1: f32, radius_2: f32, merge_dimension: MergeDimension) -> f32 {
+    match merge_dimension {
+        MergeDimension::Area => area_to_radius(radius_to_area(radius_1) + radius_to_area(radius_2)),
+        MergeDimension::Volume => {
+            volume_to_radius(radius_to_volume(radius_1) + radius_to_volume(radius_2))
+        }
+    }
+}
+
+/// Orbital energy per unit mass of a body relative to a central mass, negative for bound
+/// (elliptic) orbits and positive for escaping ones.
+pub fn specific_orbital_energy(mu: f32, relative_position: Vec2, relative_velocity: Vec2) -> f32 {
+    0.5 * relative_velocity.length_squared() - mu / relative_position.length()
+}
+
+/// Osculating Keplerian orbit of a body relative to a central mass, see `orbital_elements`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrbitalElements {
+    /// Negative for hyperbolic (escaping) orbits
+    pub semi_major_axis: f32,
+    pub eccentricity: f32,
+    /// Direction of the periapsis in radians
+    pub periapsis_angle: f32,
+    /// Time of one revolution, `None` when the orbit isn't bound
+    pub period: Option<f32>,
+}
+
+/// Orbit a body with the given position and velocity relative to a central mass would follow
+/// without other bodies around. For two bodies `mu` is G times the sum of both masses.
+/// `None` with repulsive gravity or when the bodies are at the same place.
+pub fn orbital_elements(
+    mu: f32,
+    relative_position: Vec2,
+    relative_velocity: Vec2,
+) -> Option<OrbitalElements> {
+    let r = relative_position.length();
+    if mu <= 0.0 || r == 0.0 {
+        return None;
+    }
+    let energy = specific_orbital_energy(mu, relative_position, relative_velocity);
+    // eccentricity vector e = ((v² − mu/r)·r − (r·v)·v) / mu, pointing to the periapsis
+    let eccentricity = ((relative_velocity.length_squared() - mu / r) * relative_position
+        - relative_position.dot(relative_velocity) * relative_velocity)
+        / mu;
+    let semi_major_axis = -mu / (2.0 * energy);
+    Some(OrbitalElements {
+        semi_major_axis,
+        eccentricity: eccentricity.length(),
+        periapsis_angle: eccentricity.y.atan2(eccentricity.x),
+        period: (energy < 0.0).then(|| kepler_period(mu, semi_major_axis)),
+    })
+}
+
+/// Velocity of a body at distance `r` from a central mass on a Keplerian orbit with the given
+/// eccentricity, seen at `true_anomaly` radians past the periapsis.
+/// Returned as (radial, tangential) components, for eccentricity 0 this is the circular speed.
+/// There are no bound orbits with repulsive gravity (`mu <= 0`), the velocity is zero then.
+pub fn orbital_velocity(mu: f32, r: f32, eccentricity: f32, true_anomaly: f32) -> Vec2 {
+    if mu <= 0.0 {
+        return Vec2::ZERO;
+    }
+    let semi_latus_rectum = r * (1.0 + eccentricity * true_anomaly.cos());
+    let speed = (mu / semi_latus_rectum).sqrt();
+    Vec2::new(
+        speed * eccentricity * true_anomaly.sin(),
+        speed * (1.0 + eccentricity * true_anomaly.cos()),
+    )
+}
+
+/// Speed at distance `r` from a central mass on an orbit with the given semi-major axis,
+/// from the vis-viva equation v² = mu·(2/r − 1/a).
+pub fn vis_viva_speed(mu: f32, r: f32, semi_major_axis: f32) -> f32 {
+    (mu * (2.0 / r - 1.0 / semi_major_axis)).max(0.0).sqrt()
+}
+
+/// Speed needed at distance `r` to escape a central mass, sqrt(2·mu/r), the vis-viva speed
+/// of an infinitely large orbit. Zero with repulsive gravity.
+pub fn escape_speed(mu: f32, r: f32) -> f32 {
+    (2.0 * mu / r).max(0.0).sqrt()
+}
+
+/// Orbital period from Kepler's third law, T = 2π·sqrt(a³/mu).
+pub fn kepler_period(mu: f32, semi_major_axis: f32) -> f32 {
+    2.0 * PI * (semi_major_axis.powi(3) / mu).sqrt()
+}
+
+/// Semi-major axis of the orbit with the given period, the inverse of `kepler_period`.
+pub fn kepler_semi_major_axis(mu: f32, period: f32) -> f32 {
+    (mu * (period / (2.0 * PI)).powi(2)).cbrt()
+}
+
+/// Radius of the region where a body of `mass` at `distance` from a much heavier `sun_mass`
+/// dominates the gravity, r = a · (m / 3M)^(1/3) with the current distance used for a.
+pub fn hill_radius(distance: f32, mass: f32, sun_mass: f32) -> f32 {
+    distance * (mass / (3.0 * sun_mass)).cbrt()
+}
+
+/// Distance from `primary` under which its tides tear `secondary` apart,
+/// d = coefficient · R · (ρ_primary / ρ_secondary)^(1/3), the coefficient is 2.44 for fluid bodies.
+pub fn roche_limit(primary: &Body, secondary: &Body, coefficient: f32) -> f32 {
+    let primary_density = primary.mass / radius_to_volume(primary.radius);
+    let secondary_density = secondary.mass / radius_to_volume(secondary.radius);
+    coefficient * primary.radius * (primary_density / secondary_density).powf(1.0 / 3.0)
+}
+
+/// Maps `u` uniform in 0..1 to a sample of the power law p(m) ∝ m^-slope between `min`
+/// and `max` by inverting its cumulative distribution.
+pub fn sample_power_law(u: f32, min: f32, max: f32, slope: f32) -> f32 {
+    let exponent = 1.0 - slope;
+    if exponent.abs() < 1e-6 {
+        // p(m) ∝ 1/m is uniform in log m
+        return min * (max / min).powf(u);
+    }
+    let low = min.powf(exponent);
+    let high = max.powf(exponent);
+    (low + u * (high - low)).powf(1.0 / exponent)
+}
+
+pub fn radius_to_area(r: f32) -> f32 {
+    PI * r.powf(2.0)
+}
+
+pub fn radius_to_volume(r: f32) -> f32 {
+    4.0 / 3.0 * PI * r.powf(3.0)
+}
+
+pub fn area_to_radius(a: f32) -> f32 {
+    (a / PI).sqrt()
+}
+
+pub fn volume_to_radius(v: f32) -> f32 {
+    ((3.0 * v) / (4.0 * PI)).powf(1.0 / 3.0)
+}
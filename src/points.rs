@@ -0,0 +1,71 @@
+use crate::layers;
+use crate::quads::{fill_quads, quads_mesh, Quads};
+use crate::{Planet, RenderMode, Settings};
+use bevy::prelude::*;
+use bevy::sprite::Mesh2dHandle;
+
+/// Cheaper rendering for large numbers of planets. Instead of one tessellated circle
+/// per planet, all planets are drawn as squares of a single mesh whose vertices are
+/// rewritten every frame, so there is one entity and one draw call whatever their number.
+
+#[derive(Default)]
+pub struct PointsPlugin;
+
+impl Plugin for PointsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(spawn_point_cloud)
+            .add_system(draw_points);
+    }
+}
+
+#[derive(Component)]
+struct PointCloud;
+
+fn spawn_point_cloud(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    commands.spawn_bundle((
+        Quads::default(),
+        PointCloud,
+        Mesh2dHandle(meshes.add(quads_mesh())),
+        Transform::from_xyz(0.0, 0.0, layers::PLANETS),
+        GlobalTransform::default(),
+        Visibility { is_visible: false },
+        ComputedVisibility::default(),
+    ));
+}
+
+fn draw_points(
+    settings: Res<Settings>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut planets: Query<(&Planet, &Transform, &mut Visibility), Without<PointCloud>>,
+    mut cloud: Query<(&Mesh2dHandle, &mut Visibility), With<PointCloud>>,
+) {
+    let points = settings.render_mode == RenderMode::Points;
+    for (_, _, mut visibility) in planets.iter_mut() {
+        if visibility.is_visible == points {
+            visibility.is_visible = !points;
+        }
+    }
+
+    let (mesh, mut visibility) = match cloud.get_single_mut() {
+        Ok(cloud) => cloud,
+        Err(_) => return,
+    };
+    // there is nothing to draw in an empty mesh
+    let visible = points && !planets.is_empty();
+    if visibility.is_visible != visible {
+        visibility.is_visible = visible;
+    }
+    if !visible {
+        return;
+    }
+    if let Some(mesh) = meshes.get_mut(&mesh.0) {
+        let quads = planets.iter().map(|(planet, transform, _)| {
+            (
+                transform.translation.truncate(),
+                planet.radius,
+                planet.color,
+            )
+        });
+        fill_quads(mesh, quads);
+    }
+}
@@ -0,0 +1,286 @@
+use bevy::{
+    core::FloatOrd,
+    core_pipeline::Transparent2d,
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        mesh::Indices,
+        render_asset::RenderAssets,
+        render_phase::{AddRenderCommand, DrawFunctions, RenderPhase, SetItemPipeline},
+        render_resource::{
+            BlendComponent, BlendFactor, BlendOperation, BlendState, ColorTargetState, ColorWrites,
+            Face, FragmentState, FrontFace, MultisampleState, PolygonMode, PrimitiveState,
+            PrimitiveTopology, RenderPipelineCache, RenderPipelineDescriptor, Shader,
+            SpecializedPipeline, SpecializedPipelines, TextureFormat, VertexAttribute,
+            VertexBufferLayout, VertexFormat, VertexState, VertexStepMode,
+        },
+        texture::BevyDefault,
+        view::VisibleEntities,
+        RenderApp, RenderStage,
+    },
+    sprite::{
+        DrawMesh2d, Mesh2dHandle, Mesh2dPipeline, Mesh2dPipelineKey, Mesh2dUniform,
+        SetMesh2dBindGroup, SetMesh2dViewBindGroup,
+    },
+};
+
+/// Draws many small colored squares as a single mesh. The mesh is rebuilt from scratch
+/// with `fill_quads`, the colors are stored per vertex so one draw call covers all of them.
+/// The squares are alpha blended, or added to what is already drawn for glows.
+
+#[derive(Default)]
+pub struct QuadsPlugin;
+
+impl Plugin for QuadsPlugin {
+    fn build(&self, app: &mut App) {
+        let mut shaders = app.world.get_resource_mut::<Assets<Shader>>().unwrap();
+        shaders.set_untracked(QUADS_SHADER_HANDLE, Shader::from_wgsl(QUADS_SHADER));
+
+        let render_app = app.sub_app_mut(RenderApp);
+        render_app
+            .add_render_command::<Transparent2d, DrawQuads>()
+            .init_resource::<QuadsPipeline>()
+            .init_resource::<SpecializedPipelines<QuadsPipeline>>()
+            .add_system_to_stage(RenderStage::Extract, extract_quads)
+            .add_system_to_stage(RenderStage::Queue, queue_quads);
+    }
+}
+
+/// Marks a `Mesh2dHandle` filled with `fill_quads`, it needs the usual `Transform`,
+/// `GlobalTransform`, `Visibility` and `ComputedVisibility` to be drawn.
+#[derive(Component, Clone, Copy, Default)]
+pub struct Quads {
+    /// Add the colors, weighted by their alpha, to the frame instead of blending over it,
+    /// overlapping squares brighten up to white
+    pub additive: bool,
+}
+
+/// Replaces the content of `mesh` with one square per item, given by its center,
+/// half of its side and its color.
+pub fn fill_quads(mesh: &mut Mesh, quads: impl Iterator<Item = (Vec2, f32, Color)>) {
+    let mut positions = Vec::new();
+    let mut colors = Vec::new();
+    let mut indices = Vec::new();
+    for (center, half_size, color) in quads {
+        let first = positions.len() as u32;
+        for corner in [
+            Vec2::new(-1.0, -1.0),
+            Vec2::new(1.0, -1.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(-1.0, 1.0),
+        ] {
+            let position = center + corner * half_size;
+            positions.push([position.x, position.y, 0.0]);
+            colors.push(color.as_linear_rgba_u32());
+        }
+        // counterclockwise, the back faces are culled
+        indices.extend_from_slice(&[first, first + 1, first + 2, first, first + 2, first + 3]);
+    }
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    mesh.set_indices(Some(Indices::U32(indices)));
+}
+
+/// Empty mesh for a new `Quads` entity
+pub fn quads_mesh() -> Mesh {
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    fill_quads(&mut mesh, std::iter::empty());
+    mesh
+}
+
+const QUADS_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 7_316_902_514_386_200_577);
+
+/// Positions go through the usual 2D view and mesh transforms, the color is unpacked
+/// from the `u32` of `Color::as_linear_rgba_u32`.
+const QUADS_SHADER: &str = r"
+#import bevy_sprite::mesh2d_view_bind_group
+[[group(0), binding(0)]]
+var<uniform> view: View;
+#import bevy_sprite::mesh2d_struct
+[[group(1), binding(0)]]
+var<uniform> mesh: Mesh2d;
+
+struct Vertex {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] color: u32;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] color: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = view.view_proj * mesh.model * vec4<f32>(vertex.position, 1.0);
+    out.color = vec4<f32>((vec4<u32>(vertex.color) >> vec4<u32>(0u, 8u, 16u, 24u)) & vec4<u32>(255u)) / 255.0;
+    return out;
+}
+
+struct FragmentInput {
+    [[location(0)]] color: vec4<f32>;
+};
+
+[[stage(fragment)]]
+fn fragment(in: FragmentInput) -> [[location(0)]] vec4<f32> {
+    return in.color;
+}
+";
+
+struct QuadsPipeline {
+    mesh2d_pipeline: Mesh2dPipeline,
+}
+
+impl FromWorld for QuadsPipeline {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            mesh2d_pipeline: Mesh2dPipeline::from_world(world),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct QuadsPipelineKey {
+    mesh: Mesh2dPipelineKey,
+    additive: bool,
+}
+
+/// `src * alpha + dst` for the color, the alpha of the target accumulates as usual
+const ADDITIVE_BLENDING: BlendState = BlendState {
+    color: BlendComponent {
+        src_factor: BlendFactor::SrcAlpha,
+        dst_factor: BlendFactor::One,
+        operation: BlendOperation::Add,
+    },
+    alpha: BlendComponent::OVER,
+};
+
+impl SpecializedPipeline for QuadsPipeline {
+    type Key = QuadsPipelineKey;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        // the mesh sorts its attributes by name, "Vertex_Color" comes before "Vertex_Position"
+        let vertex_attributes = vec![
+            VertexAttribute {
+                format: VertexFormat::Float32x3,
+                offset: 4,
+                shader_location: 0,
+            },
+            VertexAttribute {
+                format: VertexFormat::Uint32,
+                offset: 0,
+                shader_location: 1,
+            },
+        ];
+        RenderPipelineDescriptor {
+            vertex: VertexState {
+                shader: QUADS_SHADER_HANDLE.typed::<Shader>(),
+                entry_point: "vertex".into(),
+                shader_defs: Vec::new(),
+                buffers: vec![VertexBufferLayout {
+                    array_stride: 16,
+                    step_mode: VertexStepMode::Vertex,
+                    attributes: vertex_attributes,
+                }],
+            },
+            fragment: Some(FragmentState {
+                shader: QUADS_SHADER_HANDLE.typed::<Shader>(),
+                shader_defs: Vec::new(),
+                entry_point: "fragment".into(),
+                targets: vec![ColorTargetState {
+                    format: TextureFormat::bevy_default(),
+                    blend: Some(if key.additive {
+                        ADDITIVE_BLENDING
+                    } else {
+                        BlendState::ALPHA_BLENDING
+                    }),
+                    write_mask: ColorWrites::ALL,
+                }],
+            }),
+            layout: Some(vec![
+                self.mesh2d_pipeline.view_layout.clone(),
+                self.mesh2d_pipeline.mesh_layout.clone(),
+            ]),
+            primitive: PrimitiveState {
+                front_face: FrontFace::Ccw,
+                cull_mode: Some(Face::Back),
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+                topology: key.mesh.primitive_topology(),
+                strip_index_format: None,
+            },
+            depth_stencil: None,
+            multisample: MultisampleState {
+                count: key.mesh.msaa_samples(),
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            label: Some("quads_pipeline".into()),
+        }
+    }
+}
+
+type DrawQuads = (
+    SetItemPipeline,
+    SetMesh2dViewBindGroup<0>,
+    SetMesh2dBindGroup<1>,
+    DrawMesh2d,
+);
+
+fn extract_quads(
+    mut commands: Commands,
+    mut previous_len: Local<usize>,
+    query: Query<(Entity, &Quads, &ComputedVisibility)>,
+) {
+    let mut values = Vec::with_capacity(*previous_len);
+    for (entity, quads, computed_visibility) in query.iter() {
+        if computed_visibility.is_visible {
+            values.push((entity, (*quads,)));
+        }
+    }
+    *previous_len = values.len();
+    commands.insert_or_spawn_batch(values);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn queue_quads(
+    draw_functions: Res<DrawFunctions<Transparent2d>>,
+    quads_pipeline: Res<QuadsPipeline>,
+    mut pipelines: ResMut<SpecializedPipelines<QuadsPipeline>>,
+    mut pipeline_cache: ResMut<RenderPipelineCache>,
+    msaa: Res<Msaa>,
+    render_meshes: Res<RenderAssets<Mesh>>,
+    quads: Query<(&Mesh2dHandle, &Mesh2dUniform, &Quads)>,
+    mut views: Query<(&VisibleEntities, &mut RenderPhase<Transparent2d>)>,
+) {
+    if quads.is_empty() {
+        return;
+    }
+    let draw_quads = draw_functions.read().get_id::<DrawQuads>().unwrap();
+    for (visible_entities, mut phase) in views.iter_mut() {
+        for entity in visible_entities.entities.iter() {
+            if let Ok((mesh_handle, uniform, style)) = quads.get(*entity) {
+                let mesh = match render_meshes.get(&mesh_handle.0) {
+                    Some(mesh) => mesh,
+                    None => continue,
+                };
+                let key = QuadsPipelineKey {
+                    mesh: Mesh2dPipelineKey::from_msaa_samples(msaa.samples)
+                        | Mesh2dPipelineKey::from_primitive_topology(mesh.primitive_topology),
+                    additive: style.additive,
+                };
+                phase.add(Transparent2d {
+                    entity: *entity,
+                    draw_function: draw_quads,
+                    pipeline: pipelines.specialize(&mut pipeline_cache, &quads_pipeline, key),
+                    // sorted by depth together with the shapes and sprites
+                    sort_key: FloatOrd(uniform.transform.w_axis.z),
+                    batch_range: None,
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use crate::layers;
+use crate::{spawn_planet, Planet, SimulationLabel, Velocity};
+use bevy::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+/// Records the planets for a while and plays the recording back, optionally in a loop.
+/// During playback the simulation is paused and the planets are moved, created and removed
+/// as in the recording, stopping it continues the simulation from the frame shown last.
+
+#[derive(Default)]
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Replay::default())
+            .add_system(record_frame.after(SimulationLabel::Gravity))
+            .add_system(play_frame);
+    }
+}
+
+/// Recording stops by itself after this many frames to bound the memory use
+const MAX_RECORDED_FRAMES: usize = 1200;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ReplayMode {
+    Idle,
+    Recording,
+    Playing,
+}
+
+impl Default for ReplayMode {
+    fn default() -> Self {
+        ReplayMode::Idle
+    }
+}
+
+struct RecordedBody {
+    /// `Entity::to_bits` at the time of the recording, unique even when ids are reused
+    id: u64,
+    position: Vec2,
+    velocity: Vec2,
+}
+
+#[derive(Default)]
+pub struct Replay {
+    mode: ReplayMode,
+    pub looping: bool,
+    /// Planets never change after spawning so they are stored once per entity
+    planets: HashMap<u64, Planet>,
+    frames: Vec<Vec<RecordedBody>>,
+    /// Next frame to play
+    frame: usize,
+    /// Planets spawned by the playback for the recorded ids
+    live: HashMap<u64, Entity>,
+    /// The simulated planets are removed before the first frame is played
+    clear_pending: bool,
+}
+
+impl Replay {
+    pub fn mode(&self) -> ReplayMode {
+        self.mode
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn record(&mut self) {
+        self.planets.clear();
+        self.frames.clear();
+        self.mode = ReplayMode::Recording;
+    }
+
+    pub fn play(&mut self) {
+        if self.frames.is_empty() {
+            return;
+        }
+        self.frame = 0;
+        self.live.clear();
+        self.clear_pending = true;
+        self.mode = ReplayMode::Playing;
+    }
+
+    pub fn stop(&mut self) {
+        self.mode = ReplayMode::Idle;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.mode == ReplayMode::Playing
+    }
+}
+
+fn record_frame(
+    mut replay: ResMut<Replay>,
+    planets: Query<(Entity, &Planet, &Transform, &Velocity)>,
+) {
+    if replay.mode != ReplayMode::Recording {
+        return;
+    }
+    let mut frame = Vec::new();
+    for (entity, planet, transform, velocity) in planets.iter() {
+        let id = entity.to_bits();
+        replay.planets.entry(id).or_insert_with(|| planet.clone());
+        frame.push(RecordedBody {
+            id,
+            position: transform.translation.truncate(),
+            velocity: velocity.0,
+        });
+    }
+    replay.frames.push(frame);
+    if replay.frames.len() >= MAX_RECORDED_FRAMES {
+        info!("Recording stopped after {} frames", MAX_RECORDED_FRAMES);
+        replay.mode = ReplayMode::Idle;
+    }
+}
+
+fn play_frame(
+    mut commands: Commands,
+    mut replay: ResMut<Replay>,
+    mut planets: Query<(Entity, &mut Transform, &mut Velocity), With<Planet>>,
+) {
+    if replay.mode != ReplayMode::Playing {
+        return;
+    }
+    if replay.frame >= replay.frames.len() {
+        if replay.looping {
+            replay.frame = 0;
+        } else {
+            replay.mode = ReplayMode::Idle;
+            return;
+        }
+    }
+    let replay = &mut *replay;
+    if replay.clear_pending {
+        replay.clear_pending = false;
+        for (entity, _, _) in planets.iter() {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    let frame = &replay.frames[replay.frame];
+    let present: HashSet<u64> = frame.iter().map(|body| body.id).collect();
+    // bodies merged or removed in the recording
+    replay.live.retain(|id, entity| {
+        let keep = present.contains(id);
+        if !keep {
+            commands.entity(*entity).despawn();
+        }
+        keep
+    });
+    for body in frame {
+        match replay.live.get(&body.id) {
+            Some(entity) => {
+                if let Ok((_, mut live_transform, mut velocity)) = planets.get_mut(*entity) {
+                    live_transform.translation.x = body.position.x;
+                    live_transform.translation.y = body.position.y;
+                    velocity.0 = body.velocity;
+                }
+            }
+            None => {
+                let entity = spawn_planet(
+                    &mut commands,
+                    replay.planets[&body.id].clone(),
+                    Velocity(body.velocity),
+                    Transform::from_xyz(body.position.x, body.position.y, layers::PLANETS),
+                );
+                replay.live.insert(body.id, entity);
+            }
+        }
+    }
+    replay.frame += 1;
+}
@@ -0,0 +1,84 @@
+//! Scenes described in a TOML file, see README for the schema.
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Scene {
+    #[serde(default)]
+    pub settings: SceneSettings,
+    #[serde(default, rename = "body")]
+    pub bodies: Vec<SceneBody>,
+}
+
+/// Settings overridden while the scene is built, the missing ones are left alone.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SceneSettings {
+    pub g: Option<f32>,
+    pub time_step: Option<f32>,
+    pub collisions: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SceneBody {
+    pub position: [f32; 2],
+    #[serde(default)]
+    pub velocity: [f32; 2],
+    pub radius: f32,
+    #[serde(default = "default_density")]
+    pub density: f32,
+    /// Linear RGB in 0..1
+    #[serde(default = "default_color")]
+    pub color: [f32; 3],
+    #[serde(default)]
+    pub is_sun: bool,
+}
+
+fn default_density() -> f32 {
+    1.0
+}
+
+fn default_color() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+pub fn parse(source: &str) -> Result<Scene, String> {
+    toml::from_str(source).map_err(|err| format!("invalid scene: {}", err))
+}
+
+/// Packs the scene into a string short enough to paste in a chat: deflated JSON in base64.
+pub fn encode(scene: &Scene) -> String {
+    let json = serde_json::to_vec(scene).expect("scenes are always serializable");
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+    encoder
+        .write_all(&json)
+        .and_then(|_| encoder.finish())
+        .map(|compressed| base64::encode_config(compressed, base64::URL_SAFE_NO_PAD))
+        .expect("writing to memory can't fail")
+}
+
+/// Unpacks a scene made by `encode`, surrounding whitespace is ignored.
+pub fn decode(code: &str) -> Result<Scene, String> {
+    let compressed = base64::decode_config(code.trim(), base64::URL_SAFE_NO_PAD)
+        .map_err(|err| format!("invalid scene code: {}", err))?;
+    let mut json = Vec::new();
+    DeflateDecoder::new(compressed.as_slice())
+        .read_to_end(&mut json)
+        .map_err(|err| format!("invalid scene code: {}", err))?;
+    serde_json::from_slice(&json).map_err(|err| format!("invalid scene code: {}", err))
+}
+
+/// Reads the scene from `path`. Files can't be read in the browser so the web build fails.
+pub fn load(path: &str) -> Result<Scene, String> {
+    parse(&read(path)?)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read(path: &str) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|err| format!("can't read {}: {}", path, err))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read(path: &str) -> Result<String, String> {
+    Err(format!("can't read {} in the browser", path))
+}
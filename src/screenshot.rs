@@ -0,0 +1,264 @@
+use crate::Settings;
+use bevy::{
+    prelude::*,
+    render::{
+        camera::{CameraPlugin, ExtractedCamera},
+        render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext},
+        render_resource::{
+            Buffer, BufferDescriptor, BufferUsages, Extent3d, ImageCopyBuffer, ImageDataLayout,
+            MapMode, Texture, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+            TextureView, TextureViewDescriptor,
+        },
+        renderer::{RenderContext, RenderDevice},
+        texture::BevyDefault,
+        view::{ExtractedWindows, ViewTarget},
+        RenderApp, RenderStage,
+    },
+    window::WindowId,
+};
+use std::num::NonZeroU32;
+
+/// Saves the current frame with the "Screenshot" button or the F12 key. For that one frame
+/// the window is rendered into a texture instead of the swap chain, the texture is copied
+/// into a buffer after the egui pass and read back once the frame is rendered, so the web
+/// build doesn't depend on the canvas keeping its drawing buffer. The desktop build writes
+/// a timestamped PNG into the working directory, the web build downloads it.
+/// `Settings::screenshot_ui` decides whether the egui windows are drawn into the picture.
+
+#[derive(Default)]
+pub struct ScreenshotPlugin;
+
+impl Plugin for ScreenshotPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Screenshot>()
+            .init_resource::<ScreenshotRequest>()
+            .add_system(screenshot_key)
+            .add_system(request_screenshot);
+
+        let render_app = app.sub_app_mut(RenderApp);
+        render_app
+            .add_system_to_stage(RenderStage::Extract, extract_screenshot_request)
+            .add_system_to_stage(RenderStage::Queue, redirect_to_capture)
+            .add_system_to_stage(RenderStage::Cleanup, save_capture);
+        let mut graph = render_app.world.get_resource_mut::<RenderGraph>().unwrap();
+        graph.add_node(SCREENSHOT_NODE, ScreenshotNode);
+        graph
+            .add_node_edge(bevy_egui::node::EGUI_PASS, SCREENSHOT_NODE)
+            .unwrap();
+    }
+}
+
+pub struct Screenshot;
+
+const SCREENSHOT_NODE: &str = "screenshot";
+
+/// wgpu copies textures into buffers in rows aligned to 256 bytes
+const COPY_BYTES_PER_ROW_ALIGNMENT: u32 = 256;
+
+/// Screenshot asked for in this frame, `Some(include_ui)` until the render world takes it
+#[derive(Default)]
+struct ScreenshotRequest(Option<bool>);
+
+/// Render world copy of the `ScreenshotRequest`
+struct CaptureRequest {
+    include_ui: bool,
+}
+
+/// Texture the frame is rendered into and the buffer it's read back from
+struct Capture {
+    texture: Texture,
+    buffer: Buffer,
+    size: Extent3d,
+    padded_bytes_per_row: u32,
+}
+
+fn screenshot_key(keys: Res<Input<KeyCode>>, mut ev_screenshot: EventWriter<Screenshot>) {
+    if keys.just_pressed(KeyCode::F12) {
+        ev_screenshot.send(Screenshot);
+    }
+}
+
+fn request_screenshot(
+    mut ev_screenshot: EventReader<Screenshot>,
+    settings: Res<Settings>,
+    mut request: ResMut<ScreenshotRequest>,
+) {
+    if ev_screenshot.iter().count() > 0 {
+        request.0 = Some(settings.screenshot_ui);
+    }
+}
+
+fn extract_screenshot_request(mut commands: Commands, mut request: ResMut<ScreenshotRequest>) {
+    if let Some(include_ui) = request.0.take() {
+        commands.insert_resource(CaptureRequest { include_ui });
+    }
+}
+
+/// Points the 2D camera, and with the UI the swap chain egui draws into, to the capture
+/// texture. Runs after the view targets are prepared. The skipped swap chain frame is
+/// dropped instead of presented, the window keeps showing the previous frame.
+fn redirect_to_capture(
+    mut commands: Commands,
+    request: Option<Res<CaptureRequest>>,
+    render_device: Res<RenderDevice>,
+    mut windows: ResMut<ExtractedWindows>,
+    mut cameras: Query<(&ExtractedCamera, &mut ViewTarget)>,
+) {
+    let request = match request {
+        Some(request) => request,
+        None => return,
+    };
+    commands.remove_resource::<CaptureRequest>();
+    let window = match windows.windows.get_mut(&WindowId::primary()) {
+        Some(window) if window.swap_chain_texture.is_some() => window,
+        _ => return,
+    };
+    let size = Extent3d {
+        width: window.physical_width,
+        height: window.physical_height,
+        depth_or_array_layers: 1,
+    };
+    let (texture, view) = frame_texture(&render_device, size);
+    // egui draws into the picture or into a scratch texture nobody looks at
+    window.swap_chain_texture = Some(if request.include_ui {
+        view.clone()
+    } else {
+        frame_texture(&render_device, size).1
+    });
+    for (camera, mut target) in cameras.iter_mut() {
+        if camera.window_id == WindowId::primary()
+            && (request.include_ui || camera.name.as_deref() == Some(CameraPlugin::CAMERA_2D))
+        {
+            target.view = view.clone();
+        }
+    }
+    let padded_bytes_per_row = {
+        let bytes_per_row = size.width * 4;
+        let padding = (COPY_BYTES_PER_ROW_ALIGNMENT - bytes_per_row % COPY_BYTES_PER_ROW_ALIGNMENT)
+            % COPY_BYTES_PER_ROW_ALIGNMENT;
+        bytes_per_row + padding
+    };
+    let buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("screenshot_buffer"),
+        size: (padded_bytes_per_row * size.height) as u64,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    commands.insert_resource(Capture {
+        texture,
+        buffer,
+        size,
+        padded_bytes_per_row,
+    });
+}
+
+/// Texture with the format of the swap chain which can be copied from
+fn frame_texture(render_device: &RenderDevice, size: Extent3d) -> (Texture, TextureView) {
+    let texture = render_device.create_texture(&TextureDescriptor {
+        label: Some("screenshot_texture"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: TextureFormat::bevy_default(),
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+    });
+    let view = texture.create_view(&TextureViewDescriptor::default());
+    (texture, view)
+}
+
+/// Copies the captured frame into the readback buffer, after the main and the egui passes
+struct ScreenshotNode;
+
+impl Node for ScreenshotNode {
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        if let Some(capture) = world.get_resource::<Capture>() {
+            render_context.command_encoder.copy_texture_to_buffer(
+                capture.texture.as_image_copy(),
+                ImageCopyBuffer {
+                    buffer: &capture.buffer,
+                    layout: ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: NonZeroU32::new(capture.padded_bytes_per_row),
+                        rows_per_image: None,
+                    },
+                },
+                capture.size,
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Reads back the frame rendered in this update, the commands are already submitted
+fn save_capture(
+    mut commands: Commands,
+    capture: Option<Res<Capture>>,
+    render_device: Res<RenderDevice>,
+) {
+    let capture = match capture {
+        Some(capture) => capture,
+        None => return,
+    };
+    commands.remove_resource::<Capture>();
+    let slice = capture.buffer.slice(..);
+    render_device.map_buffer(&slice, MapMode::Read);
+    let row_bytes = (capture.size.width * 4) as usize;
+    let mut pixels = Vec::with_capacity(row_bytes * capture.size.height as usize);
+    for row in slice
+        .get_mapped_range()
+        .chunks(capture.padded_bytes_per_row as usize)
+    {
+        pixels.extend_from_slice(&row[..row_bytes]);
+    }
+    capture.buffer.unmap();
+    if TextureFormat::bevy_default() == TextureFormat::Bgra8UnormSrgb {
+        for pixel in pixels.chunks_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+    if let Err(err) = save_png(&pixels, capture.size.width, capture.size.height) {
+        warn!("Screenshot failed: {}", err);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn save_png(pixels: &[u8], width: u32, height: u32) -> Result<(), String> {
+    use wasm_bindgen::JsCast;
+
+    let mut png = Vec::new();
+    image::codecs::png::PngEncoder::new(&mut png)
+        .encode(pixels, width, height, image::ColorType::Rgba8)
+        .map_err(|err| err.to_string())?;
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or("no document")?;
+    let link = document
+        .create_element("a")
+        .ok()
+        .and_then(|link| link.dyn_into::<web_sys::HtmlAnchorElement>().ok())
+        .ok_or("can't create a link")?;
+    let timestamp: String = js_sys::Date::new_0().to_iso_string().into();
+    link.set_href(&format!("data:image/png;base64,{}", base64::encode(&png)));
+    link.set_download(&format!("nbody-{}.png", timestamp.replace(':', "-")));
+    link.click();
+    Ok(())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save_png(pixels: &[u8], width: u32, height: u32) -> Result<(), String> {
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|err| err.to_string())?
+        .as_millis();
+    let path = format!("nbody-{}.png", timestamp);
+    image::save_buffer(&path, pixels, width, height, image::ColorType::Rgba8)
+        .map_err(|err| err.to_string())?;
+    info!("Screenshot saved to {}", path);
+    Ok(())
+}
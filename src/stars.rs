@@ -0,0 +1,75 @@
+use crate::layers;
+use crate::pancam::PanCam;
+use crate::{Settings, Stats};
+use bevy::{prelude::*, render::camera::OrthographicProjection};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// Static star-field drawn behind the simulation. Stars keep their size on screen
+/// regardless of zoom and scroll slightly slower than the camera (parallax).
+
+#[derive(Default)]
+pub struct StarFieldPlugin;
+
+impl Plugin for StarFieldPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(spawn_stars).add_system(move_stars);
+    }
+}
+
+const N_STARS: usize = 600;
+/// Size of the area (in pixels) the stars are scattered in, wraps around when scrolling
+const FIELD_SIZE: f32 = 4000.0;
+/// Fraction of the camera movement applied to the stars
+const PARALLAX: f32 = 0.05;
+
+#[derive(Component)]
+struct Star {
+    /// Position on screen in pixels relative to the window center
+    offset: Vec2,
+}
+
+fn spawn_stars(mut commands: Commands, settings: Res<Settings>) {
+    let mut rng = StdRng::seed_from_u64(settings.seed);
+    for _ in 0..N_STARS {
+        let offset = Vec2::new(rng.gen::<f32>() - 0.5, rng.gen::<f32>() - 0.5) * FIELD_SIZE;
+        let brightness = rng.gen::<f32>() * 0.4 + 0.2;
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: Color::rgb(brightness, brightness, brightness),
+                    custom_size: Some(Vec2::new(1.5, 1.5)),
+                    ..Default::default()
+                },
+                transform: Transform::from_xyz(offset.x, offset.y, layers::STARS),
+                ..Default::default()
+            })
+            .insert(Star { offset });
+    }
+}
+
+fn wrap(value: f32) -> f32 {
+    (value + FIELD_SIZE / 2.0).rem_euclid(FIELD_SIZE) - FIELD_SIZE / 2.0
+}
+
+fn move_stars(
+    stats: Res<Stats>,
+    camera: Query<(&Transform, &OrthographicProjection), With<PanCam>>,
+    mut stars: Query<(&Star, &mut Transform, &mut Visibility), Without<PanCam>>,
+) {
+    let (camera_transform, projection) = match camera.iter().next() {
+        Some(camera) => camera,
+        None => return,
+    };
+    let camera_position = camera_transform.translation.truncate();
+    let shift = camera_position * PARALLAX;
+    for (star, mut transform, mut visibility) in stars.iter_mut() {
+        visibility.is_visible = stats.draw_stars;
+        let on_screen = Vec2::new(wrap(star.offset.x - shift.x), wrap(star.offset.y - shift.y));
+        // stars are not part of the tilted plane, undo the camera stretch
+        let scale = projection.scale * camera_transform.scale.truncate();
+        let position = camera_position + on_screen * scale;
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+        transform.scale = scale.extend(1.0);
+    }
+}
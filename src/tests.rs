@@ -0,0 +1,1372 @@
+//! Runs the simulation systems in a headless app with known initial states.
+use super::*;
+
+fn test_settings() -> Settings {
+    Settings {
+        seed: 42,
+        ..Settings::default()
+    }
+}
+
+/// App without windows or rendering which only runs the `gravity` system.
+fn gravity_app(settings: Settings) -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .insert_resource(settings)
+        .insert_resource(Stats::default())
+        .insert_resource(Replay::default())
+        .insert_resource(MergeLog::default())
+        .add_system(gravity.label(SimulationLabel::Gravity));
+    app
+}
+
+/// App without windows or rendering which builds the scene of `settings` once.
+fn reset_app(settings: Settings) -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .insert_resource(settings)
+        .insert_resource(Stats::default())
+        .insert_resource(MergeLog::default())
+        .add_event::<Reset>()
+        .add_system(setup_many_orbits);
+    app.world
+        .get_resource_mut::<Events<Reset>>()
+        .unwrap()
+        .send(Reset);
+    app.update();
+    app
+}
+
+fn spawn_body(app: &mut App, radius: f32, position: Vec2, velocity: Vec2) -> Entity {
+    app.world
+        .spawn()
+        .insert(Planet {
+            radius,
+            density: 1.0,
+            color: Color::WHITE,
+            is_sun: false,
+        })
+        .insert(Velocity(velocity))
+        .insert(Transform::from_xyz(position.x, position.y, layers::PLANETS))
+        .id()
+}
+
+fn assert_close(actual: f32, expected: f32) {
+    let tolerance = 1e-5 * expected.abs().max(1e-3);
+    assert!(
+        (actual - expected).abs() <= tolerance,
+        "{} is not close to {}",
+        actual,
+        expected
+    );
+}
+
+#[test]
+fn two_bodies_attract_each_other() {
+    let settings = test_settings();
+    let g = settings.g;
+    let dt = 1.0 / settings.time_step;
+    let mut app = gravity_app(settings);
+    let first = spawn_body(&mut app, 2.0, Vec2::new(-50.0, 0.0), Vec2::ZERO);
+    let second = spawn_body(&mut app, 4.0, Vec2::new(50.0, 0.0), Vec2::ZERO);
+
+    app.update();
+
+    // a = g·m_other / d², semi-implicit Euler updates the velocity first: v = a·dt, x += v·dt
+    let mass_1 = physics::radius_to_volume(2.0);
+    let mass_2 = physics::radius_to_volume(4.0);
+    let speed_1 = g * mass_2 / 100.0f32.powf(2.0) * dt;
+    let speed_2 = g * mass_1 / 100.0f32.powf(2.0) * dt;
+
+    let velocity_1 = app.world.get::<Velocity>(first).unwrap().0;
+    let velocity_2 = app.world.get::<Velocity>(second).unwrap().0;
+    assert_close(velocity_1.x, speed_1);
+    assert_close(velocity_2.x, -speed_2);
+    assert_eq!(velocity_1.y, 0.0);
+    assert_eq!(velocity_2.y, 0.0);
+
+    let position_1 = app.world.get::<Transform>(first).unwrap().translation;
+    let position_2 = app.world.get::<Transform>(second).unwrap().translation;
+    assert_close(position_1.x, -50.0 + speed_1 * dt);
+    assert_close(position_2.x, 50.0 - speed_2 * dt);
+}
+
+#[test]
+fn touching_bodies_merge_preserving_mass_and_momentum() {
+    let mut app = gravity_app(Settings {
+        collisions: true,
+        ..test_settings()
+    });
+    spawn_body(&mut app, 2.0, Vec2::new(-1.0, 0.0), Vec2::new(3.0, 0.0));
+    spawn_body(&mut app, 3.0, Vec2::new(1.0, 0.0), Vec2::new(0.0, -2.0));
+
+    app.update();
+
+    let merged: Vec<(Planet, Vec2)> = app
+        .world
+        .query::<(&Planet, &Velocity)>()
+        .iter(&app.world)
+        .map(|(planet, velocity)| (planet.clone(), velocity.0))
+        .collect();
+    assert_eq!(merged.len(), 1);
+    let (planet, velocity) = &merged[0];
+
+    let mass_1 = physics::radius_to_volume(2.0);
+    let mass_2 = physics::radius_to_volume(3.0);
+    assert_close(planet.mass(), mass_1 + mass_2);
+    let momentum = Vec2::new(3.0, 0.0) * mass_1 + Vec2::new(0.0, -2.0) * mass_2;
+    assert_close(velocity.x * planet.mass(), momentum.x);
+    assert_close(velocity.y * planet.mass(), momentum.y);
+}
+
+#[test]
+fn area_merge_keeps_the_total_area() {
+    // 3² + 4² = 5², the area of both planets counts whichever comes first
+    assert_close(physics::merge_radius(3.0, 4.0, MergeDimension::Area), 5.0);
+    assert_close(physics::merge_radius(4.0, 3.0, MergeDimension::Area), 5.0);
+    assert_close(
+        physics::merge_radius(1.0, 1.0, MergeDimension::Area),
+        2.0f32.sqrt(),
+    );
+}
+
+#[test]
+fn merged_body_keeps_the_id_of_the_heavier_parent() {
+    let mut app = gravity_app(Settings {
+        collisions: true,
+        ..test_settings()
+    });
+    let light = spawn_body(&mut app, 1.0, Vec2::new(-1.0, 0.0), Vec2::ZERO);
+    let heavy = spawn_body(&mut app, 3.0, Vec2::new(1.0, 0.0), Vec2::ZERO);
+    app.world.entity_mut(light).insert(BodyId(7));
+    app.world.entity_mut(heavy).insert(BodyId(9));
+
+    app.update();
+
+    let ids: Vec<BodyId> = app
+        .world
+        .query_filtered::<&BodyId, With<Planet>>()
+        .iter(&app.world)
+        .copied()
+        .collect();
+    assert_eq!(ids, vec![BodyId(9)]);
+    let log = &app.world.get_resource::<MergeLog>().unwrap().0;
+    assert_eq!(log.len(), 1);
+    assert_eq!(log[0].survivor, BodyId(9));
+    assert_eq!(log[0].absorbed, BodyId(7));
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[test]
+fn merges_are_written_to_the_csv_log() {
+    let path = std::env::temp_dir().join(format!("nbody-merges-{}.csv", std::process::id()));
+    let mut app = gravity_app(Settings {
+        collisions: true,
+        log_merges: true,
+        merge_log_path: path.to_string_lossy().into_owned(),
+        ..test_settings()
+    });
+    app.add_plugin(MergeLogPlugin);
+    let light = spawn_body(&mut app, 1.0, Vec2::new(-1.0, 0.0), Vec2::new(2.0, 0.0));
+    let heavy = spawn_body(&mut app, 3.0, Vec2::new(1.0, 0.0), Vec2::ZERO);
+    app.world.entity_mut(light).insert(BodyId(7));
+    app.world.entity_mut(heavy).insert(BodyId(9));
+
+    app.update();
+
+    let csv = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(
+        lines[0],
+        "sim_time,survivor_mass,absorbed_mass,merged_mass,impact_speed"
+    );
+    let values: Vec<f32> = lines[1]
+        .split(',')
+        .map(|value| value.parse().unwrap())
+        .collect();
+    let (heavy_mass, light_mass) = (
+        physics::radius_to_volume(3.0),
+        physics::radius_to_volume(1.0),
+    );
+    assert_eq!(values.len(), 5);
+    assert_eq!(values[0], 0.0);
+    assert_close(values[1], heavy_mass);
+    assert_close(values[2], light_mass);
+    assert_close(values[3], heavy_mass + light_mass);
+    assert_close(values[4], 2.0);
+}
+
+#[test]
+fn merge_in_a_later_substep_happens_where_the_bodies_met() {
+    let mut app = gravity_app(Settings {
+        collisions: true,
+        g: 0.0,
+        time_step: 1.0,
+        substeps: 4,
+        ..test_settings()
+    });
+    // they first touch at the start of the last substep, at x = 0.75 and x = 2.25
+    spawn_body(&mut app, 1.0, Vec2::new(-3.0, 0.0), Vec2::new(5.0, 0.0));
+    spawn_body(&mut app, 2.0, Vec2::new(3.0, 0.0), Vec2::new(-1.0, 0.0));
+
+    app.update();
+
+    let positions: Vec<Vec2> = app
+        .world
+        .query_filtered::<&Transform, With<Planet>>()
+        .iter(&app.world)
+        .map(|transform| transform.translation.truncate())
+        .collect();
+    assert_eq!(positions.len(), 1);
+    let (light_mass, heavy_mass) = (
+        physics::radius_to_volume(1.0),
+        physics::radius_to_volume(2.0),
+    );
+    let barycenter = (0.75 * light_mass + 2.25 * heavy_mass) / (light_mass + heavy_mass);
+    assert_close(positions[0].x, barycenter);
+    assert_eq!(positions[0].y, 0.0);
+}
+
+#[test]
+fn planet_merged_twice_in_one_frame_keeps_its_identity() {
+    let mut app = gravity_app(Settings {
+        collisions: true,
+        g: 0.0,
+        time_step: 1.0,
+        substeps: 4,
+        ..test_settings()
+    });
+    // the first two touch in the second substep, the merged planet meets the third one
+    // in the last substep
+    let first = spawn_body(&mut app, 1.2, Vec2::new(0.0, 0.0), Vec2::ZERO);
+    let second = spawn_body(&mut app, 1.0, Vec2::new(2.5, 0.0), Vec2::new(-2.0, 0.0));
+    let third = spawn_body(&mut app, 1.3, Vec2::new(-4.6, 0.0), Vec2::new(4.0, 0.0));
+    for (i, entity) in [first, second, third].into_iter().enumerate() {
+        app.world
+            .entity_mut(entity)
+            .insert(BodyId(i as u64 + 1))
+            .insert(Lineage {
+                spawn_time: 0.0,
+                merges: 0,
+            });
+    }
+    app.world.entity_mut(first).insert(Name::new("First"));
+
+    app.update();
+
+    let planets: Vec<(BodyId, Lineage, Name)> = app
+        .world
+        .query_filtered::<(&BodyId, &Lineage, &Name), With<Planet>>()
+        .iter(&app.world)
+        .map(|(id, lineage, name)| (*id, *lineage, name.clone()))
+        .collect();
+    assert_eq!(planets.len(), 1);
+    let (id, lineage, name) = &planets[0];
+    assert_eq!(*id, BodyId(1));
+    assert_eq!(lineage.merges, 2);
+    assert_eq!(name.as_str(), "First");
+    let merges: Vec<(BodyId, BodyId)> = app
+        .world
+        .get_resource::<MergeLog>()
+        .unwrap()
+        .0
+        .iter()
+        .map(|record| (record.survivor, record.absorbed))
+        .collect();
+    assert_eq!(merges, vec![(BodyId(1), BodyId(2)), (BodyId(1), BodyId(3))]);
+}
+
+#[test]
+fn circular_orbits_without_eccentricity() {
+    let settings = Settings {
+        n_objects: 50,
+        max_eccentricity: 0.0,
+        ..test_settings()
+    };
+    let sun_mass = physics::radius_to_volume(settings.sun_size) * settings.sun_density;
+    let mu = settings.g * sun_mass;
+    let mut app = reset_app(settings);
+
+    let planets: Vec<(Vec2, Vec2)> = app
+        .world
+        .query::<(&Planet, &Velocity, &Transform)>()
+        .iter(&app.world)
+        .filter(|(planet, _, _)| !planet.is_sun)
+        .map(|(_, velocity, transform)| (transform.translation.truncate(), velocity.0))
+        .collect();
+    assert_eq!(planets.len(), 50);
+    for (position, velocity) in planets {
+        // tangential and counterclockwise at sqrt(G·M/r), as before the eccentricity
+        let radial = velocity.dot(position.normalize());
+        let tangential = position.normalize().perp_dot(velocity);
+        let circular_speed = (mu / position.length()).sqrt();
+        assert!(radial.abs() < 1e-4 * circular_speed, "radial {}", radial);
+        assert!((tangential - circular_speed).abs() < 1e-4 * circular_speed);
+    }
+}
+
+#[test]
+fn ring_scenario_turns_the_collisions_off_without_touching_the_settings() {
+    let mut app = reset_app(Settings {
+        scenario: Scenario::Ring,
+        ring_particles: 10,
+        collisions: true,
+        ..test_settings()
+    });
+    let collisions = |app: &App| {
+        let settings = app.world.get_resource::<Settings>().unwrap();
+        let stats = app.world.get_resource::<Stats>().unwrap();
+        (
+            settings.collisions,
+            force_params(settings, stats.scenario).collisions,
+        )
+    };
+    assert_eq!(collisions(&app), (true, false));
+
+    app.world.get_resource_mut::<Settings>().unwrap().scenario = Scenario::ManyOrbits;
+    app.world
+        .get_resource_mut::<Events<Reset>>()
+        .unwrap()
+        .send(Reset);
+    app.update();
+    assert_eq!(collisions(&app), (true, true));
+}
+
+#[test]
+fn resonances_scenario_keeps_the_collision_setting() {
+    let app = reset_app(Settings {
+        scenario: Scenario::Resonances,
+        resonance_particles: 10,
+        collisions: true,
+        ..test_settings()
+    });
+    let settings = app.world.get_resource::<Settings>().unwrap();
+    let stats = app.world.get_resource::<Stats>().unwrap();
+    assert!(settings.collisions);
+    assert!(!force_params(settings, stats.scenario).collisions);
+}
+
+#[test]
+fn leapfrog_start_keeps_the_energy_better_than_a_synchronized_start() {
+    let params = force_params(
+        &Settings {
+            collisions: false,
+            ..test_settings()
+        },
+        None,
+    );
+    let body = |position: Vec2, velocity: Vec2, mass: f32| Body {
+        position,
+        velocity,
+        mass,
+        radius: 1.0,
+        fixed: false,
+        is_sun: false,
+    };
+    let (mass, orbit) = (1000.0, 100.0);
+    let mu = params.g * mass;
+    let initial = [
+        body(Vec2::ZERO, Vec2::ZERO, mass),
+        body(
+            Vec2::new(orbit, 0.0),
+            physics::orbital_velocity(mu, orbit, 0.0, 0.0),
+            1.0,
+        ),
+    ];
+    let period = physics::kepler_period(mu, orbit);
+    let dt = period / 200.0;
+    let initial_energy = physics::total_energy(&initial, params.g);
+
+    // largest relative energy error over ten orbits
+    let energy_error = |integrator: Integrator| {
+        let mut bodies = initial.to_vec();
+        if integrator == Integrator::Leapfrog {
+            let forces = physics::compute_forces(&bodies, &params);
+            physics::leapfrog_start(&mut bodies, &forces, dt);
+        }
+        let mut worst = 0.0f32;
+        for _ in 0..2000 {
+            let forces = physics::compute_forces(&bodies, &params);
+            physics::advance(&mut bodies, &forces, dt, integrator);
+            // the velocities are half a step behind the positions, the energy is
+            // measured with both at the same time
+            let forces = physics::compute_forces(&bodies, &params);
+            let synchronized: Vec<Body> = bodies
+                .iter()
+                .zip(forces.accelerations.iter())
+                .map(|(body, acceleration)| Body {
+                    velocity: body.velocity + *acceleration * dt / 2.0,
+                    ..*body
+                })
+                .collect();
+            let energy = physics::total_energy(&synchronized, params.g);
+            worst = worst.max(((energy - initial_energy) / initial_energy).abs());
+        }
+        worst
+    };
+    let naive = energy_error(Integrator::SemiImplicitEuler);
+    let leapfrog = energy_error(Integrator::Leapfrog);
+    assert!(
+        leapfrog * 10.0 < naive,
+        "leapfrog error {} against {}",
+        leapfrog,
+        naive
+    );
+}
+
+#[test]
+fn blended_merge_of_equal_red_and_blue_planets_is_purple() {
+    let settings = Settings {
+        blend_colors_on_merge: true,
+        ..test_settings()
+    };
+    let planet = |color: Color, is_sun: bool| Planet {
+        radius: 2.0,
+        density: 1.0,
+        color,
+        is_sun,
+    };
+    let merged = merge_planets(
+        &planet(Color::RED, false),
+        &planet(Color::BLUE, false),
+        &settings,
+    );
+    let [r, g, b, a] = merged.color.as_linear_rgba_f32();
+    assert_close(r, 0.5);
+    assert_close(g, 0.0);
+    assert_close(b, 0.5);
+    assert_close(a, 1.0);
+
+    // even a heavier planet doesn't tint the sun
+    let heavy = Planet {
+        radius: 5.0,
+        ..planet(Color::RED, false)
+    };
+    let sun = planet(Color::YELLOW, true);
+    assert_eq!(merge_planets(&heavy, &sun, &settings).color, Color::YELLOW);
+    assert_eq!(merge_planets(&sun, &heavy, &settings).color, Color::YELLOW);
+}
+
+#[test]
+fn seeded_runs_end_the_same_whatever_the_entity_ids() {
+    let run = |recycled_entities: usize| {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(Settings {
+                n_objects: 300,
+                max_planet_size: 6.0,
+                max_planet_orbit_radius: 400.0,
+                collisions: true,
+                ..test_settings()
+            })
+            .insert_resource(Stats::default())
+            .insert_resource(Replay::default())
+            .insert_resource(MergeLog::default())
+            .insert_resource(BodyIdCounter::default())
+            .add_event::<Reset>()
+            .add_system_to_stage(CoreStage::PreUpdate, assign_body_ids)
+            .add_system(gravity.label(SimulationLabel::Gravity))
+            .add_system(setup_many_orbits.after(SimulationLabel::Gravity));
+        // freed entities are handed out again in reverse, which reverses the entity order
+        let spare: Vec<Entity> = (0..recycled_entities)
+            .map(|_| app.world.spawn().id())
+            .collect();
+        for entity in spare {
+            app.world.despawn(entity);
+        }
+        app.world
+            .get_resource_mut::<Events<Reset>>()
+            .unwrap()
+            .send(Reset);
+        for _ in 0..201 {
+            app.update();
+        }
+        let mut planets: Vec<(BodyId, Vec2)> = app
+            .world
+            .query_filtered::<(&BodyId, &Transform), With<Planet>>()
+            .iter(&app.world)
+            .map(|(id, transform)| (*id, transform.translation.truncate()))
+            .collect();
+        planets.sort_by_key(|(id, _)| id.0);
+        planets
+    };
+    let first = run(0);
+    let second = run(1000);
+    assert_eq!(first.len(), second.len());
+    assert_eq!(first, second);
+}
+
+#[test]
+fn power_law_samples_stay_in_range_and_favor_small_masses() {
+    let samples: Vec<f32> = (0..1000)
+        .map(|i| physics::sample_power_law(i as f32 / 999.0, 1.0, 1000.0, 2.35))
+        .collect();
+    assert_close(samples[0], 1.0);
+    assert_close(samples[999], 1000.0);
+    assert!(samples.windows(2).all(|pair| pair[0] <= pair[1]));
+    // with slope 2.35 most of the planets are lighter than 10
+    let light = samples.iter().filter(|mass| **mass < 10.0).count();
+    assert!(light > 900, "only {} light samples", light);
+}
+
+#[test]
+fn velocities_follow_the_square_root_of_g() {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .insert_resource(Settings {
+            g: 2.0,
+            rescale_on_g_change: true,
+            ..test_settings()
+        })
+        .add_system(rescale_on_g_change);
+    let planet = spawn_body(&mut app, 1.0, Vec2::ZERO, Vec2::new(3.0, 4.0));
+    app.update();
+    assert_eq!(app.world.get::<Velocity>(planet).unwrap().0, Vec2::new(3.0, 4.0));
+
+    app.world.get_resource_mut::<Settings>().unwrap().g = 8.0;
+    app.update();
+    let velocity = app.world.get::<Velocity>(planet).unwrap().0;
+    assert_close(velocity.x, 6.0);
+    assert_close(velocity.y, 8.0);
+}
+
+#[test]
+fn impact_debris_preserves_mass_and_momentum() {
+    let mut app = gravity_app(Settings {
+        collisions: true,
+        impact_debris: true,
+        debris_speed_threshold: 1.0,
+        debris_count: 4,
+        ..test_settings()
+    });
+    spawn_body(&mut app, 3.0, Vec2::new(-1.0, 0.0), Vec2::new(5.0, 0.0));
+    spawn_body(&mut app, 2.0, Vec2::new(1.0, 0.0), Vec2::new(-5.0, 1.0));
+    let mass_1 = physics::radius_to_volume(3.0);
+    let mass_2 = physics::radius_to_volume(2.0);
+    let momentum = Vec2::new(5.0, 0.0) * mass_1 + Vec2::new(-5.0, 1.0) * mass_2;
+
+    app.update();
+
+    let planets: Vec<(f32, Vec2)> = app
+        .world
+        .query::<(&Planet, &Velocity)>()
+        .iter(&app.world)
+        .map(|(planet, velocity)| {
+            // the merged planet loses the debris volume, not density
+            assert_close(planet.density, 1.0);
+            (planet.mass(), velocity.0)
+        })
+        .collect();
+    assert_eq!(planets.len(), 5);
+    let total_mass: f32 = planets.iter().map(|(mass, _)| mass).sum();
+    let total_momentum: Vec2 = planets
+        .iter()
+        .map(|(mass, velocity)| *velocity * *mass)
+        .sum();
+    assert_close(total_mass, mass_1 + mass_2);
+    assert!((total_momentum - momentum).length() < 1e-2 * momentum.length());
+}
+
+#[test]
+fn si_suffixes_keep_three_integer_digits_up_to_tera() {
+    assert_eq!(format_number(12.345, NumberFormat::SiSuffix), "12.35");
+    assert_eq!(format_number(-45_600.0, NumberFormat::SiSuffix), "-45.60k");
+    assert_eq!(format_number(7.0e9, NumberFormat::SiSuffix), "7.00G");
+    assert_eq!(format_number(1.0e18, NumberFormat::SiSuffix), "1000000.00T");
+}
+
+#[test]
+fn merged_body_sums_the_merge_counts_of_its_parents() {
+    let mut app = gravity_app(Settings {
+        collisions: true,
+        ..test_settings()
+    });
+    let light = spawn_body(&mut app, 1.0, Vec2::new(-1.0, 0.0), Vec2::ZERO);
+    let heavy = spawn_body(&mut app, 3.0, Vec2::new(1.0, 0.0), Vec2::ZERO);
+    app.world.entity_mut(light).insert(Lineage {
+        spawn_time: 1.0,
+        merges: 2,
+    });
+    app.world.entity_mut(heavy).insert(Lineage {
+        spawn_time: 5.0,
+        merges: 3,
+    });
+
+    app.update();
+
+    let lineages: Vec<Lineage> = app
+        .world
+        .query_filtered::<&Lineage, With<Planet>>()
+        .iter(&app.world)
+        .copied()
+        .collect();
+    assert_eq!(lineages.len(), 1);
+    assert_eq!(lineages[0].merges, 6);
+    // the light parent is older
+    assert_eq!(lineages[0].spawn_time, 1.0);
+}
+
+#[test]
+fn body_bounces_elastically_off_the_arena_walls() {
+    let half_size = Vec2::new(100.0, 50.0);
+    let mut body = Body {
+        position: Vec2::new(97.0, 0.0),
+        velocity: Vec2::new(10.0, 1.0),
+        mass: 1.0,
+        radius: 2.0,
+        fixed: false,
+        is_sun: false,
+    };
+    // the edge crossed the wall at x = 100 by 9
+    body.position += body.velocity;
+    physics::bounce_off_walls(&mut body, half_size);
+    assert_eq!(body.velocity, Vec2::new(-10.0, 1.0));
+    assert_close(body.position.x, 89.0);
+    assert_close(body.position.y, 1.0);
+
+    // into the corner, both components flip and the body stays inside
+    body.position = Vec2::new(95.0, 45.0);
+    body.velocity = Vec2::new(5.0, 5.0);
+    body.position += body.velocity;
+    physics::bounce_off_walls(&mut body, half_size);
+    assert_eq!(body.velocity, Vec2::new(-5.0, -5.0));
+    assert!(body.position.x.abs() <= half_size.x - body.radius);
+    assert!(body.position.y.abs() <= half_size.y - body.radius);
+}
+
+#[test]
+fn prediction_stops_the_path_of_an_absorbed_body() {
+    let body = |x: f32, velocity: f32, radius: f32| Body {
+        position: Vec2::new(x, 0.0),
+        velocity: Vec2::new(velocity, 0.0),
+        mass: physics::radius_to_volume(radius),
+        radius,
+        fixed: false,
+        is_sun: false,
+    };
+    let bodies = [
+        body(-20.0, 100.0, 0.5),
+        body(20.0, 0.0, 3.0),
+        body(0.0, 0.0, 1.0),
+    ];
+    let params = force_params(
+        &Settings {
+            g: 0.0,
+            collisions: true,
+            ..test_settings()
+        },
+        None,
+    );
+    let paths = physics::predict_paths(&bodies, &params, 0.01, 100, 1, MergeDimension::Volume);
+    // the first body hits the heavier third one after 20 steps
+    assert!(paths[0].len() < 30);
+    assert!(paths[2].len() > 100);
+    assert_eq!(paths[1].len(), 101);
+    assert_eq!(bodies[0].position, Vec2::new(-20.0, 0.0));
+}
+
+#[test]
+fn merge_policy_lets_planets_pass_through_suns() {
+    let body = |x: f32, is_sun: bool| Body {
+        position: Vec2::new(x, 0.0),
+        velocity: Vec2::ZERO,
+        mass: 1.0,
+        radius: 2.0,
+        fixed: false,
+        is_sun,
+    };
+    let mut params = force_params(
+        &Settings {
+            collisions: true,
+            ..test_settings()
+        },
+        None,
+    );
+    params.merge_policy.planets_with_suns = false;
+
+    let forces = physics::compute_forces(&[body(0.0, true), body(1.0, false)], &params);
+    assert!(forces.collisions.is_empty());
+    let forces = physics::compute_forces(&[body(0.0, false), body(1.0, false)], &params);
+    assert_eq!(forces.collisions.len(), 1);
+}
+
+#[test]
+fn bundled_scene_parses_with_defaults() {
+    let scene = scene::parse(include_str!("../assets/scenes/binary_star.toml")).unwrap();
+    assert_eq!(scene.settings.g, Some(3.5));
+    assert_eq!(scene.bodies.len(), 3);
+    assert!(scene.bodies[0].is_sun);
+    let planet = &scene.bodies[2];
+    assert!(!planet.is_sun);
+    assert_eq!(planet.density, 1.0);
+}
+
+#[test]
+fn scene_code_round_trips_the_bundled_scene() {
+    let scene = scene::parse(include_str!("../assets/scenes/binary_star.toml")).unwrap();
+    let code = scene::encode(&scene);
+    assert!(!code.contains(char::is_whitespace));
+
+    let decoded = scene::decode(&format!(" {}\n", code)).unwrap();
+    assert_eq!(decoded.settings.time_step, scene.settings.time_step);
+    assert_eq!(decoded.bodies.len(), scene.bodies.len());
+    assert_eq!(decoded.bodies[0].velocity, scene.bodies[0].velocity);
+    assert!(scene::decode("not a scene").is_err());
+}
+
+#[test]
+fn overlapping_bodies_are_pushed_apart() {
+    let mut app = gravity_app(Settings {
+        g: 0.0,
+        collisions: false,
+        resolve_overlaps: true,
+        overlap_push: 0.5,
+        ..test_settings()
+    });
+    let light = spawn_body(&mut app, 1.0, Vec2::new(0.0, 0.0), Vec2::ZERO);
+    let heavy = spawn_body(&mut app, 2.0, Vec2::new(1.0, 0.0), Vec2::ZERO);
+
+    for _ in 0..30 {
+        app.update();
+    }
+
+    let position = |entity: Entity| {
+        app.world
+            .get::<Transform>(entity)
+            .unwrap()
+            .translation
+            .truncate()
+    };
+    let (light, heavy) = (position(light), position(heavy));
+    assert!(light.distance(heavy) > 3.0 * 0.999);
+    // the lighter body moved more, the center of mass stayed in place
+    let (light_mass, heavy_mass) = (
+        physics::radius_to_volume(1.0),
+        physics::radius_to_volume(2.0),
+    );
+    let barycenter = (light * light_mass + heavy * heavy_mass) / (light_mass + heavy_mass);
+    assert!(barycenter.distance(Vec2::new(heavy_mass / (light_mass + heavy_mass), 0.0)) < 1e-3);
+    assert_eq!(light.y, 0.0);
+}
+
+#[test]
+fn collision_heat_is_the_kinetic_energy_lost_in_the_merge() {
+    let body = |mass: f32, velocity: Vec2| Body {
+        position: Vec2::ZERO,
+        velocity,
+        mass,
+        radius: 1.0,
+        fixed: false,
+        is_sun: false,
+    };
+    let body_1 = body(2.0, Vec2::new(3.0, 1.0));
+    let body_2 = body(5.0, Vec2::new(-4.0, 2.0));
+    let kinetic_energy = |mass: f32, velocity: Vec2| 0.5 * mass * velocity.length_squared();
+    let before =
+        kinetic_energy(body_1.mass, body_1.velocity) + kinetic_energy(body_2.mass, body_2.velocity);
+    let after = kinetic_energy(
+        body_1.mass + body_2.mass,
+        physics::merge_velocity(&body_1, &body_2),
+    );
+
+    assert_close(physics::collision_heat(&body_1, &body_2), before - after);
+}
+
+#[test]
+fn substeps_keep_the_pace_of_the_simulation() {
+    let mut app = gravity_app(Settings {
+        substeps: 4,
+        ..test_settings()
+    });
+    let body = spawn_body(&mut app, 1.0, Vec2::ZERO, Vec2::new(12.0, 0.0));
+
+    app.update();
+
+    let time_step = test_settings().time_step;
+    let transform = app.world.get::<Transform>(body).unwrap();
+    assert_close(transform.translation.x, 12.0 / time_step);
+    assert_close(transform.translation.y, 0.0);
+}
+
+#[test]
+fn launched_planet_follows_the_requested_orbit() {
+    let settings = Settings {
+        launch_semi_major_axis: 500.0,
+        launch_eccentricity: 0.3,
+        launch_periapsis_angle: 30.0,
+        ..test_settings()
+    };
+    let sun_mass = physics::radius_to_volume(settings.sun_size) * settings.sun_density;
+    let mu = settings.g * sun_mass;
+    let (position, velocity) = launch_state(&settings, sun_mass);
+    let mut bodies = vec![
+        Body {
+            position: Vec2::ZERO,
+            velocity: Vec2::ZERO,
+            mass: sun_mass,
+            radius: settings.sun_size,
+            fixed: true,
+            is_sun: true,
+        },
+        Body {
+            position,
+            velocity,
+            mass: 1.0,
+            radius: 1.0,
+            fixed: false,
+            is_sun: false,
+        },
+    ];
+    let params = force_params(&settings, None);
+
+    // one whole orbit, leapfrog keeps the orbit from drifting
+    let steps = 2000;
+    let dt = physics::kepler_period(mu, 500.0) / steps as f32;
+    let forces = physics::compute_forces(&bodies, &params);
+    physics::leapfrog_start(&mut bodies, &forces, dt);
+    for _ in 0..steps {
+        let forces = physics::compute_forces(&bodies, &params);
+        physics::advance(&mut bodies, &forces, dt, Integrator::Leapfrog);
+    }
+
+    let elements = physics::orbital_elements(mu, bodies[1].position, bodies[1].velocity).unwrap();
+    assert!(
+        (elements.semi_major_axis - 500.0).abs() < 5.0,
+        "semi-major axis {}",
+        elements.semi_major_axis
+    );
+    assert!((elements.eccentricity - 0.3).abs() < 0.01);
+    assert!(bodies[1].position.distance(position) < 5.0);
+}
+
+#[test]
+fn orbital_elements_of_an_elliptic_orbit_at_periapsis() {
+    let (mu, periapsis, eccentricity) = (1000.0, 10.0, 0.5);
+    let semi_major_axis = periapsis / (1.0 - eccentricity);
+    // at the periapsis the velocity is perpendicular to the radius
+    let speed = physics::vis_viva_speed(mu, periapsis, semi_major_axis);
+    let elements =
+        physics::orbital_elements(mu, Vec2::new(0.0, periapsis), Vec2::new(-speed, 0.0)).unwrap();
+
+    assert_close(elements.semi_major_axis, semi_major_axis);
+    assert_close(elements.eccentricity, eccentricity);
+    assert_close(elements.periapsis_angle, PI / 2.0);
+    assert_close(
+        elements.period.unwrap(),
+        2.0 * PI * (semi_major_axis.powi(3) / mu).sqrt(),
+    );
+    assert!(physics::orbital_elements(-mu, Vec2::X, Vec2::Y).is_none());
+}
+
+#[test]
+fn skipped_frames_are_caught_up_by_a_longer_step() {
+    let mut app = gravity_app(Settings {
+        physics_every_n_frames: 3,
+        ..test_settings()
+    });
+    let body = spawn_body(&mut app, 1.0, Vec2::ZERO, Vec2::new(12.0, 0.0));
+
+    app.update();
+    app.update();
+    assert_eq!(app.world.get::<Transform>(body).unwrap().translation.x, 0.0);
+    app.update();
+
+    let time_step = test_settings().time_step;
+    let transform = app.world.get::<Transform>(body).unwrap();
+    assert_close(transform.translation.x, 3.0 * 12.0 / time_step);
+}
+
+#[test]
+fn total_mass_is_kept_across_a_merge() {
+    let mut app = gravity_app(Settings {
+        collisions: true,
+        ..test_settings()
+    });
+    spawn_body(&mut app, 2.0, Vec2::new(-1.0, 0.0), Vec2::ZERO);
+    spawn_body(&mut app, 3.0, Vec2::new(1.0, 0.0), Vec2::ZERO);
+
+    app.update();
+    let before = app.world.get_resource::<Stats>().unwrap().total_mass;
+    app.update();
+
+    let stats = app.world.get_resource::<Stats>().unwrap();
+    assert_eq!(stats.collision_count, 1);
+    assert_close(
+        before,
+        physics::radius_to_volume(2.0) + physics::radius_to_volume(3.0),
+    );
+    assert_close(stats.total_mass, before);
+}
+
+#[test]
+fn resonant_orbit_radius_follows_keplers_third_law() {
+    let mu = 3.5 * 1000.0;
+    let planet_orbit = 500.0;
+    let period = physics::kepler_period(mu, planet_orbit) / 2.0;
+    // a particle orbiting twice per planet orbit (2:1) lies at (1/2)^(2/3) of its radius
+    assert_close(
+        physics::kepler_semi_major_axis(mu, period),
+        planet_orbit * 0.5f32.powf(2.0 / 3.0),
+    );
+}
+
+#[test]
+fn camera_target_falls_back_to_the_sun_once_every_planet_is_gone() {
+    let mut app = gravity_app(Settings {
+        collisions: true,
+        ..test_settings()
+    });
+    let sun = spawn_body(&mut app, 5.0, Vec2::new(100.0, 0.0), Vec2::ZERO);
+    app.world.get_mut::<Planet>(sun).unwrap().is_sun = true;
+    spawn_body(&mut app, 1.0, Vec2::new(105.5, 0.0), Vec2::ZERO);
+
+    app.update();
+    assert_close(
+        app.world
+            .get_resource::<Stats>()
+            .unwrap()
+            .largest_position
+            .x,
+        105.5,
+    );
+    app.update();
+    app.update();
+
+    let stats = app.world.get_resource::<Stats>().unwrap();
+    assert_eq!(stats.collision_count, 1);
+    assert_eq!(stats.n_objects, 1);
+    // the merged sun sits near the barycenter, not at the last planet position
+    assert!((stats.largest_position.x - 100.0).abs() < 0.1);
+    assert!(stats.largest_position.y.abs() < 0.1);
+}
+
+#[test]
+fn gravity_keeps_the_acceleration_of_every_planet() {
+    let settings = test_settings();
+    let g = settings.g;
+    let mut app = gravity_app(settings);
+    let first = spawn_body(&mut app, 2.0, Vec2::new(-50.0, 0.0), Vec2::ZERO);
+    let second = spawn_body(&mut app, 4.0, Vec2::new(50.0, 0.0), Vec2::ZERO);
+
+    app.update();
+
+    let acceleration_1 = app.world.get::<Acceleration>(first).unwrap().0;
+    let acceleration_2 = app.world.get::<Acceleration>(second).unwrap().0;
+    assert_close(
+        acceleration_1.x,
+        g * physics::radius_to_volume(4.0) / 100.0f32.powf(2.0),
+    );
+    assert_close(
+        acceleration_2.x,
+        -g * physics::radius_to_volume(2.0) / 100.0f32.powf(2.0),
+    );
+}
+
+#[test]
+fn collision_grid_finds_the_same_collisions_as_the_all_pairs_check() {
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut bodies: Vec<Body> = (0..400)
+        .map(|i| Body {
+            position: Vec2::new(rng.gen_range(-200.0..200.0), rng.gen_range(-200.0..200.0)),
+            velocity: Vec2::ZERO,
+            mass: rng.gen_range(0.1..10.0),
+            radius: rng.gen_range(0.5..6.0),
+            fixed: false,
+            is_sun: i % 50 == 0,
+        })
+        .collect();
+    bodies[3].fixed = true;
+    let mut params = force_params(
+        &Settings {
+            collisions: true,
+            merge_overlap: 0.8,
+            ..test_settings()
+        },
+        None,
+    );
+    params.merge_policy.suns_with_suns = false;
+
+    for test_particle_mass in [None, Some(5.0)] {
+        params.test_particle_mass = test_particle_mass;
+        params.collision_grid = false;
+        let all_pairs = physics::compute_forces(&bodies, &params);
+        params.collision_grid = true;
+        let grid = physics::compute_forces(&bodies, &params);
+
+        assert!(all_pairs.collisions.len() > 10);
+        assert_eq!(grid.collisions, all_pairs.collisions);
+        assert_eq!(grid.merged, all_pairs.merged);
+    }
+}
+
+#[test]
+fn volume_merge_keeps_the_total_volume() {
+    // 3³ + 4³ + 5³ = 6³
+    let radius = physics::merge_radius(
+        physics::merge_radius(3.0, 4.0, MergeDimension::Volume),
+        5.0,
+        MergeDimension::Volume,
+    );
+    assert_close(radius, 6.0);
+    assert_close(
+        physics::merge_radius(1.0, 1.0, MergeDimension::Volume),
+        2.0f32.cbrt(),
+    );
+}
+
+#[test]
+fn merged_planet_keeps_the_mass_with_either_merge_dimension() {
+    let planet = |radius: f32| Planet {
+        radius,
+        density: 1.5,
+        color: Color::WHITE,
+        is_sun: false,
+    };
+    for merge_dimension in [MergeDimension::Area, MergeDimension::Volume] {
+        let settings = Settings {
+            merge_dimension,
+            ..test_settings()
+        };
+        let merged = merge_planets(&planet(2.0), &planet(3.0), &settings);
+        assert_close(merged.mass(), planet(2.0).mass() + planet(3.0).mass());
+    }
+}
+
+#[test]
+fn stats_keep_the_total_momentum_for_the_arrow() {
+    let mut app = gravity_app(test_settings());
+    spawn_body(&mut app, 2.0, Vec2::new(-50.0, 0.0), Vec2::new(1.0, 0.0));
+    spawn_body(&mut app, 3.0, Vec2::new(50.0, 0.0), Vec2::new(0.0, -2.0));
+
+    app.update();
+
+    let momentum = app.world.get_resource::<Stats>().unwrap().momentum;
+    assert_close(momentum.x, physics::radius_to_volume(2.0));
+    assert_close(momentum.y, -2.0 * physics::radius_to_volume(3.0));
+}
+
+#[test]
+fn gas_drag_circularizes_an_eccentric_orbit() {
+    let params = force_params(
+        &Settings {
+            collisions: false,
+            ..test_settings()
+        },
+        None,
+    );
+    let mu = params.g * 1000.0;
+    let eccentricity = |bodies: &[Body]| {
+        physics::orbital_elements(mu, bodies[1].position, bodies[1].velocity)
+            .unwrap()
+            .eccentricity
+    };
+    for (k, max_eccentricity) in [(0.0, 0.51), (0.01, 0.2)] {
+        let mut bodies = vec![
+            Body {
+                position: Vec2::ZERO,
+                velocity: Vec2::ZERO,
+                mass: 1000.0,
+                radius: 1.0,
+                fixed: true,
+                is_sun: true,
+            },
+            // 1.5 times the circular speed gives e = 0.5 at periapsis
+            Body {
+                position: Vec2::new(100.0, 0.0),
+                velocity: Vec2::new(0.0, (1.5 * mu / 100.0).sqrt()),
+                mass: 0.001,
+                radius: 1.0,
+                fixed: false,
+                is_sun: false,
+            },
+        ];
+        assert_close(eccentricity(&bodies), 0.5);
+        for _ in 0..20000 {
+            let mut forces = physics::compute_forces(&bodies, &params);
+            physics::apply_gas_drag(&bodies, &mut forces, params.g, k);
+            physics::integrate(&mut bodies, &forces, 0.01);
+        }
+        assert!(eccentricity(&bodies) < max_eccentricity);
+    }
+}
+
+#[test]
+fn viridis_palette_spans_the_whole_map() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let color = |index: usize, rng: &mut StdRng| palette_color(Palette::Viridis, index, 9, rng);
+    let first = VIRIDIS[0];
+    let middle = VIRIDIS[2];
+    let last = VIRIDIS[4];
+    assert_eq!(color(0, &mut rng), Color::rgb(first[0], first[1], first[2]));
+    assert_eq!(
+        color(4, &mut rng),
+        Color::rgb(middle[0], middle[1], middle[2])
+    );
+    let end = color(8, &mut rng);
+    assert_close(end.r(), last[0]);
+    assert_close(end.g(), last[1]);
+    assert_close(end.b(), last[2]);
+    assert_eq!(palette_color(Palette::White, 3, 9, &mut rng), Color::WHITE);
+}
+
+#[test]
+fn slow_motion_shortens_the_step_near_the_closest_pair() {
+    let settings = Settings {
+        g: 0.0,
+        collisions: false,
+        slow_motion: true,
+        slow_motion_distance: 100.0,
+        slow_motion_factor: 0.1,
+        ..test_settings()
+    };
+    let dt = 1.0 / settings.time_step;
+    let mut app = gravity_app(settings);
+    spawn_body(&mut app, 1.0, Vec2::new(-5.0, 0.0), Vec2::ZERO);
+    spawn_body(&mut app, 1.0, Vec2::new(5.0, 0.0), Vec2::ZERO);
+
+    // the first step finds the closest pair, the second one slows down
+    app.update();
+    let before = app.world.get_resource::<Stats>().unwrap().sim_time;
+    app.update();
+    let after = app.world.get_resource::<Stats>().unwrap().sim_time;
+
+    assert_close((after - before) as f32, dt * (0.1 + 0.9 * 0.1));
+}
+
+#[test]
+fn merged_planet_keeps_the_name_of_a_named_parent() {
+    let mut app = gravity_app(Settings {
+        collisions: true,
+        ..test_settings()
+    });
+    let light = spawn_body(&mut app, 1.0, Vec2::new(-1.0, 0.0), Vec2::ZERO);
+    spawn_body(&mut app, 3.0, Vec2::new(1.0, 0.0), Vec2::ZERO);
+    app.world.entity_mut(light).insert(Name::new("Jupiter"));
+
+    app.update();
+
+    let names: Vec<String> = app
+        .world
+        .query_filtered::<&Name, With<Planet>>()
+        .iter(&app.world)
+        .map(|name| name.as_str().to_string())
+        .collect();
+    assert_eq!(names, vec!["Jupiter".to_string()]);
+}
+
+#[test]
+fn escape_speed_leaves_zero_orbital_energy() {
+    let mu = 3.5 * 1000.0;
+    let speed = physics::escape_speed(mu, 200.0);
+    assert_close(speed, (mu / 100.0).sqrt());
+    let energy = physics::specific_orbital_energy(mu, Vec2::new(200.0, 0.0), Vec2::new(0.0, speed));
+    assert!(energy.abs() < 1e-3);
+}
+
+#[test]
+fn pause_on_collision_stops_before_the_merge() {
+    let mut app = gravity_app(Settings {
+        collisions: true,
+        pause_on_collision: true,
+        ..test_settings()
+    });
+    spawn_body(&mut app, 2.0, Vec2::new(-1.0, 0.0), Vec2::ZERO);
+    spawn_body(&mut app, 3.0, Vec2::new(1.0, 0.0), Vec2::ZERO);
+    let planet_count = |app: &mut App| {
+        app.world
+            .query_filtered::<Entity, With<Planet>>()
+            .iter(&app.world)
+            .count()
+    };
+
+    app.update();
+    app.update();
+    let stats = app.world.get_resource::<Stats>().unwrap();
+    assert!(stats.paused);
+    assert_eq!(stats.paused_collision.unwrap().position, Vec2::ZERO);
+    assert_eq!(stats.collision_count, 0);
+    assert_eq!(planet_count(&mut app), 2);
+
+    app.world.get_resource_mut::<Stats>().unwrap().step_pending = true;
+    app.update();
+    assert_eq!(planet_count(&mut app), 1);
+    assert_eq!(
+        app.world.get_resource::<Stats>().unwrap().collision_count,
+        1
+    );
+
+    // with substeps they touch in the middle of the step, at x = 0.75 and x = 2.25 after
+    // three of the four substeps
+    let mut app = gravity_app(Settings {
+        collisions: true,
+        pause_on_collision: true,
+        g: 0.0,
+        time_step: 1.0,
+        substeps: 4,
+        ..test_settings()
+    });
+    let light = spawn_body(&mut app, 1.0, Vec2::new(-3.0, 0.0), Vec2::new(5.0, 0.0));
+    let heavy = spawn_body(&mut app, 2.0, Vec2::new(3.0, 0.0), Vec2::new(-1.0, 0.0));
+
+    app.update();
+    let stats = app.world.get_resource::<Stats>().unwrap();
+    assert!(stats.paused);
+    assert_eq!(stats.sim_time, 0.75);
+    assert_eq!(
+        stats.paused_collision.unwrap().position,
+        Vec2::new(1.5, 0.0)
+    );
+    let x = |app: &App, entity: Entity| app.world.get::<Transform>(entity).unwrap().translation.x;
+    assert_eq!(x(&app, light), 0.75);
+    assert_eq!(x(&app, heavy), 2.25);
+
+    app.world.get_resource_mut::<Stats>().unwrap().step_pending = true;
+    app.update();
+    assert_eq!(planet_count(&mut app), 1);
+    assert_eq!(app.world.get_resource::<Stats>().unwrap().sim_time, 1.75);
+}
+
+#[test]
+fn energy_rescaling_reaches_the_target_and_keeps_the_momentum() {
+    let body = |x: f32, vy: f32, mass: f32| Body {
+        position: Vec2::new(x, 0.0),
+        velocity: Vec2::new(1.0, vy),
+        mass,
+        radius: 1.0,
+        fixed: false,
+        is_sun: false,
+    };
+    let mut bodies = [body(-50.0, -2.0, 10.0), body(50.0, 3.0, 5.0)];
+    let g = 3.5;
+    let target = physics::total_energy(&bodies, g) * 0.9;
+    let momentum = physics::system_totals(&bodies).momentum;
+
+    assert!(physics::rescale_energy(&mut bodies, g, target));
+
+    assert_close(physics::total_energy(&bodies, g), target);
+    let rescaled = physics::system_totals(&bodies).momentum;
+    assert_close(rescaled.x, momentum.x);
+    assert!((rescaled.y - momentum.y).abs() < 1e-4);
+}
+
+#[test]
+fn planets_cool_by_the_simulated_time() {
+    let mut app = gravity_app(Settings {
+        physics_every_n_frames: 3,
+        cooling_rate: 0.5,
+        ..test_settings()
+    });
+    app.add_system_to_stage(CoreStage::PostUpdate, cool_planets);
+    let planet = spawn_body(&mut app, 1.0, Vec2::ZERO, Vec2::ZERO);
+    app.world.entity_mut(planet).insert(Temperature(1.0));
+
+    for _ in 0..10 {
+        app.update();
+    }
+
+    let sim_time = app.world.get_resource::<Stats>().unwrap().sim_time as f32;
+    assert!(sim_time > 0.0);
+    assert_close(
+        app.world.get::<Temperature>(planet).unwrap().0,
+        (-0.5 * sim_time).exp(),
+    );
+}
+
+#[test]
+fn absorbed_ghost_drops_the_identity_of_the_planet() {
+    let mut app = gravity_app(Settings {
+        collisions: true,
+        animate_merges: true,
+        ..test_settings()
+    });
+    let light = spawn_body(&mut app, 1.0, Vec2::new(-1.0, 0.0), Vec2::ZERO);
+    let heavy = spawn_body(&mut app, 3.0, Vec2::new(1.0, 0.0), Vec2::ZERO);
+    for (entity, id) in [(light, 7), (heavy, 8)] {
+        app.world
+            .entity_mut(entity)
+            .insert(BodyId(id))
+            .insert(Name::new(format!("planet {}", id)));
+    }
+
+    app.update();
+
+    let ghosts = app
+        .world
+        .query_filtered::<Entity, With<Absorbing>>()
+        .iter(&app.world)
+        .count();
+    assert_eq!(ghosts, 1);
+    let ids: Vec<BodyId> = app
+        .world
+        .query::<&BodyId>()
+        .iter(&app.world)
+        .copied()
+        .collect();
+    assert_eq!(ids, vec![BodyId(8)]);
+    assert_eq!(app.world.query::<&Name>().iter(&app.world).count(), 1);
+}
+
+#[test]
+fn comparison_copies_are_not_counted_as_objects() {
+    let mut app = gravity_app(test_settings());
+    for x in [-10.0, 10.0] {
+        spawn_body(&mut app, 1.0, Vec2::new(x, 0.0), Vec2::ZERO);
+        let copy = spawn_body(&mut app, 1.0, Vec2::new(x, 0.0), Vec2::ZERO);
+        app.world.entity_mut(copy).insert(Comparison);
+    }
+
+    app.update();
+
+    assert_eq!(app.world.get_resource::<Stats>().unwrap().n_objects, 2);
+}
+
+#[test]
+fn body_limit_removes_the_comparison_copy_as_well() {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .insert_resource(Settings {
+            max_bodies: 1,
+            ..test_settings()
+        })
+        .add_system(limit_bodies);
+    for (radius, id) in [(1.0, 1), (2.0, 2)] {
+        let planet = spawn_body(&mut app, radius, Vec2::ZERO, Vec2::ZERO);
+        let copy = spawn_body(&mut app, radius, Vec2::ZERO, Vec2::ZERO);
+        app.world.entity_mut(planet).insert(BodyId(id));
+        app.world
+            .entity_mut(copy)
+            .insert(BodyId(id))
+            .insert(Comparison);
+    }
+
+    app.update();
+
+    let mut ids: Vec<BodyId> = app
+        .world
+        .query_filtered::<&BodyId, With<Planet>>()
+        .iter(&app.world)
+        .copied()
+        .collect();
+    ids.sort_by_key(|id| id.0);
+    assert_eq!(ids, vec![BodyId(2), BodyId(2)]);
+}
+
+#[test]
+fn duplicated_settings_keep_the_physics_settings() {
+    let settings = Settings {
+        integrator: Integrator::Leapfrog,
+        substeps: 7,
+        softening: 0.25,
+        gas_drag: 0.5,
+        ..test_settings()
+    };
+    let json = serde_json::to_string(&settings).unwrap();
+    let copy: Settings = serde_json::from_str(&json).unwrap();
+    assert_eq!(copy.integrator, Integrator::Leapfrog);
+    assert_eq!(copy.substeps, 7);
+    assert_eq!(copy.softening, 0.25);
+    assert_eq!(copy.gas_drag, 0.5);
+    assert_eq!(copy.seed, 42);
+}
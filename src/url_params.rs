@@ -0,0 +1,39 @@
+use crate::{Scenario, Settings};
+use bevy::prelude::*;
+
+/// Overrides settings from the page URL, e.g. `index.html?seed=42&n_objects=300&g=5`,
+/// so a specific scene can be shared with a link. Only available in the web build.
+
+pub fn apply_url_parameters(settings: &mut Settings) {
+    let search = match web_sys::window().map(|window| window.location().search()) {
+        Some(Ok(search)) => search,
+        _ => return,
+    };
+    for pair in search.trim_start_matches('?').split('&') {
+        if pair.is_empty() {
+            continue;
+        }
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        if let Err(err) = apply_parameter(settings, key, value) {
+            warn!("Ignoring URL parameter {}: {}", pair, err);
+        }
+    }
+}
+
+fn apply_parameter(settings: &mut Settings, key: &str, value: &str) -> Result<(), String> {
+    fn parse<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+        value
+            .parse()
+            .map_err(|_| format!("invalid value {:?}", value))
+    }
+    match key {
+        "seed" => settings.seed = parse(value)?,
+        "n_objects" => settings.n_objects = parse(value)?,
+        "g" => settings.g = parse(value)?,
+        "time_step" => settings.time_step = parse(value)?,
+        "collisions" => settings.collisions = parse(value)?,
+        "scenario" => settings.scenario = parse::<Scenario>(value)?,
+        _ => return Err("unknown parameter".to_string()),
+    }
+    Ok(())
+}